@@ -1,42 +1,380 @@
-// Example: 0x0, 0x40-0x50,0x60-0x80
+// Example: 0x0, 0x40-0x50,0x60-0x80, U+00C0..=U+00FF, 8364, "ÄÖÜ", block:Latin Extended-A, script:Hiragana, @digits
 
+use std::{collections::{BTreeMap, BTreeSet}, fmt};
+
+pub(crate) mod unicode;
+
+/// Character codes above the last Unicode code point (0x10FFFF) are never valid characters.
+const CHAR_CODE_LIMIT: u32 = 0x110000;
+
+#[derive(Debug)]
 pub struct ParseError {
+    /// The offending part of the descriptor.
     pub input: String,
-    pub message: String
+    pub message: String,
+    /// Byte offset of `input` in the descriptor, or in the definition of `charset` if the error is in a charset.
+    pub offset: usize,
+    /// The charset containing the error, when it's not in the descriptor itself.
+    pub charset: Option<String>,
+    /// What was probably meant instead of `input`, e.g. `0x4A` for `4A`.
+    pub suggestion: Option<String>
 }
 
-pub fn parse_single_charcode(char_code: &str) -> Result<u32, ParseError> {
-    // Trim spaces and ensure it starts with 0x.
-    let char_code = char_code.trim();
-    if !char_code.starts_with("0x") {
-        return Err(ParseError {
-            input: char_code.to_string(),
-            message: format!("Number {} doesn't start with 0x", char_code)
-        });
+impl ParseError {
+    fn suggest(self, suggestion: Option<String>) -> ParseError {
+        ParseError { suggestion, ..self }
     }
 
-    // Trim the 0x for parsing.
-    let char_code = char_code.trim_start_matches("0x");
+    /// The column of the error in the given descriptor, or charset definition, starting at 1.
+    pub fn column(&self, descriptor: &str) -> usize {
+        descriptor.get(..self.offset).map_or(0, |before| before.chars().count()) + 1
+    }
+}
 
-    let result = u32::from_str_radix(char_code, 16);
-    if result.is_err() {
-        return Err(ParseError {
-            input: char_code.to_string(),
-            message: format!("Number {} could not be parsed as a hexadecimal number.", char_code)
-        });
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " Did you mean {}?", suggestion)?;
+        }
+        Ok(())
     }
+}
+
+impl std::error::Error for ParseError {}
 
-    Ok(result.unwrap())
+/// Named descriptors that can be referenced from other descriptors as `@name`.
+pub type Charsets = BTreeMap<String, String>;
+
+/// Parses a descriptor such as `0x0, 0x40-0x50, U+0041..=U+005A, "ÄÖÜ"` into its items.
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    charsets: &'a Charsets,
+    /// The charsets being parsed, to detect the ones referencing themselves.
+    references: Vec<String>,
+    /// Parts of the descriptor which are valid but most likely a mistake, such as reversed ranges.
+    warnings: Vec<String>,
+    /// Whether the descriptor contains hexadecimal codes (`0x41` or `U+0041`).
+    uses_hex: bool,
+    /// The decimal codes of the descriptor along with their value, which are likely meant as hexadecimal ones
+    /// when the rest of the descriptor uses them.
+    decimal_codes: Vec<(String, u32)>
 }
 
-pub fn parse_char(char_code: u32, skip_control_characters: bool) -> Option<char> {
-    // Parse the character from u32.
-    let ch = char::from_u32(char_code);
-    if ch.is_none() {
-        return None;
+/// A single item of a descriptor.
+enum Item {
+    /// Character codes from `start` (inclusive) to `end` (exclusive).
+    Range(u32, u32),
+    /// Characters given literally between quotes, or by the name of a set such as a Unicode block.
+    Chars(Vec<char>),
+    /// The items of a charset referenced with `@name`, along with their text.
+    Group(Vec<(String, Item)>)
+}
+
+/// Prefixes of the named sets of characters, e.g. `script:Hiragana`.
+const NAMED_SETS: [&str; 3] = ["block:", "script:", "category:"];
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, charsets: &'a Charsets) -> Parser<'a> {
+        Parser { input, position: 0, charsets, references: Vec::new(), warnings: Vec::new(), uses_hex: false, decimal_codes: Vec::new() }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
     }
 
-    let ch = ch.unwrap();
+    /// Consume the given token if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            return true;
+        }
+        false
+    }
+
+    fn warn(&mut self, message: String) {
+        if !self.warnings.contains(&message) {
+            self.warnings.push(message);
+        }
+    }
+
+    /// An error about the token at the given offset.
+    fn error(&self, offset: usize, token: &str, message: String) -> ParseError {
+        ParseError {
+            input: token.to_string(),
+            message,
+            offset,
+            charset: None,
+            suggestion: None
+        }
+    }
+
+    /// Read a string between single or double quotes.
+    fn parse_literal(&mut self) -> Result<String, ParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let quote = rest.chars().next().unwrap_or_default();
+        match rest[1..].find(quote) {
+            Some(length) if length > 0 => {
+                self.position += length + 2;
+                Ok(rest[1..length + 1].to_string())
+            },
+            Some(_) => Err(self.error(self.position, &rest[..2], "Empty quotes do not contain any character.".to_string())),
+            None => Err(self.error(self.position, rest.trim_end(), format!("Missing closing quote ({}).", quote)))
+        }
+    }
+
+    /// Read a character code: `0x41`, `U+0041`, `65` or a quoted character such as `'A'`. Also tells whether
+    /// the code was written as a decimal number.
+    fn parse_value(&mut self) -> Result<(u32, bool), ParseError> {
+        self.skip_whitespace();
+        let offset = self.position;
+        if self.rest().starts_with(['"', '\'']) {
+            let literal = self.parse_literal()?;
+            let mut chars = literal.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(ch), None) => Ok((ch as u32, false)),
+                _ => Err(self.error(offset, &self.input[offset..self.position], format!("\"{}\" must be a single character to be used in a range.", literal)))
+            };
+        }
+
+        let rest = self.rest();
+        let length = rest.find(|ch: char| !ch.is_alphanumeric() && ch != '+').unwrap_or(rest.len());
+        let word = &rest[..length];
+        self.position += length;
+
+        let (digits, radix) = if let Some(digits) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            (digits, 16)
+        } else if let Some(digits) = word.strip_prefix("U+").or_else(|| word.strip_prefix("u+")) {
+            (digits, 16)
+        } else if word.starts_with(|ch: char| ch.is_ascii_digit()) {
+            (word, 10)
+        } else {
+            return Err(match (word.is_empty(), rest.trim_end()) {
+                (true, "") => self.error(offset, "", "Missing a character code at the end.".to_string()),
+                (true, rest) => self.error(offset, rest, format!("Expected a character code at '{}'.", rest)),
+                (false, _) => self.error(offset, word, format!("Character code {} should be written as 0x41, U+0041, 65 or 'A'.", word))
+                    .suggest(hex_suggestion(word))
+            });
+        };
+
+        let value = u32::from_str_radix(digits, radix).map_err(|_| match radix {
+            16 => self.error(offset, word, format!("Number {} could not be parsed as a hexadecimal number.", word)),
+            _ => self.error(offset, word, format!("Number {} could not be parsed as a decimal number.", word))
+                .suggest(hex_suggestion(word))
+        })?;
+        match radix {
+            16 => self.uses_hex = true,
+            _ => self.decimal_codes.push((word.to_string(), value))
+        }
+        Ok((value, radix == 10))
+    }
+
+    /// Read a named set of characters, such as `block:Latin-1 Supplement`, `script:Hiragana` or `category:Lu`.
+    fn parse_named_set(&mut self, prefix: &str) -> Result<Item, ParseError> {
+        self.position += prefix.len();
+        let rest = self.rest();
+        let length = rest.find([',', ';']).unwrap_or(rest.len());
+        let name = rest[..length].trim();
+        let offset = self.position + rest[..length].find(name).unwrap_or_default();
+        self.position += length;
+
+        let item = match prefix {
+            "block:" => unicode::block(name).map(|(start, end)| Item::Range(start, end)),
+            "script:" => unicode::script(name).map(Item::Chars),
+            _ => unicode::category(name).map(Item::Chars)
+        };
+        item.ok_or_else(|| {
+            let suggestion = match prefix {
+                "block:" => closest_name(name, unicode::block_names()),
+                "script:" => closest_name(name, unicode::script_names()),
+                _ => closest_name(name, unicode::category_names())
+            };
+            self.error(offset, name, format!("Unknown Unicode {} '{}'.", prefix.trim_end_matches(':'), name))
+                .suggest(suggestion.map(|suggestion| format!("{}{}", prefix, suggestion)))
+        })
+    }
+
+    /// Read a reference to a charset, such as `@latin_ext`.
+    fn parse_reference(&mut self) -> Result<Item, ParseError> {
+        let offset = self.position;
+        self.position += 1;
+        let rest = self.rest();
+        let length = rest.find(|ch: char| !ch.is_alphanumeric() && ch != '_' && ch != '-').unwrap_or(rest.len());
+        let name = &rest[..length];
+        self.position += length;
+        let token = &self.input[offset..self.position];
+
+        let descriptor = self.charsets.get(name).ok_or_else(|| {
+            let suggestion = closest_name(name, self.charsets.keys().map(String::as_str));
+            self.error(offset, token, format!("Unknown charset '@{}'.", name))
+                .suggest(suggestion.map(|suggestion| format!("@{}", suggestion)))
+        })?;
+        if self.references.iter().any(|reference| reference == name) {
+            return Err(self.error(offset, token, format!("Charset '@{}' references itself.", name)));
+        }
+
+        // Errors keep their position in the charset they are found in.
+        let mut parser = Parser::new(descriptor, self.charsets);
+        parser.references = self.references.clone();
+        parser.references.push(name.to_string());
+        let items = parser.parse_items().map_err(|error| ParseError {
+            message: format!("In charset '@{}': {}", name, error.message),
+            charset: error.charset.or_else(|| Some(name.to_string())),
+            ..error
+        })?;
+        for warning in parser.warnings {
+            self.warn(format!("In charset '@{}': {}", name, warning));
+        }
+        Ok(Item::Group(items))
+    }
+
+    fn parse_item(&mut self) -> Result<Item, ParseError> {
+        self.skip_whitespace();
+        if self.rest().starts_with('@') {
+            return self.parse_reference();
+        }
+
+        if let Some(prefix) = NAMED_SETS.iter().find(|prefix| self.rest().starts_with(*prefix)) {
+            return self.parse_named_set(prefix);
+        }
+
+        let is_literal = self.rest().starts_with(['"', '\'']);
+        let start_position = self.position;
+        if is_literal {
+            let literal = self.parse_literal()?;
+            if !self.rest().trim_start().starts_with(['-', '.']) {
+                return Ok(Item::Chars(literal.chars().collect()));
+            }
+            self.position = start_position;
+        }
+
+        let (start, start_is_decimal) = self.parse_value()?;
+        let is_inclusive = self.eat("..=");
+        let is_range = is_inclusive || self.eat("-");
+        self.skip_whitespace();
+        let end_position = self.position;
+        let (end, end_is_decimal) = match is_range {
+            true => self.parse_value()?,
+            false => (start, start_is_decimal)
+        };
+
+        let (start, end) = match (is_range, is_inclusive) {
+            // Inclusive range, e.g. 0x41..=0x5a.
+            (true, true) => (start, end.saturating_add(1)),
+            // Exclusive range, e.g. 0x41-0x5b.
+            (true, false) => (start, end),
+            _ => (start, start.saturating_add(1))
+        };
+
+        let text = self.input[start_position..self.position].trim();
+        if is_range && !is_literal && !start_is_decimal && end_is_decimal {
+            // The end of the range is already reported here.
+            self.decimal_codes.pop();
+            self.warn(format!("Range {} mixes hexadecimal and decimal numbers, did you mean {}0x{}?",
+                text, &self.input[start_position..end_position], &self.input[end_position..self.position]));
+        }
+        if end < start {
+            self.warn(format!("Range {} is reversed and does not contain any character.", text));
+        } else if end == start {
+            self.warn(format!("Range {} is empty, the end of a range using '-' is excluded.", text));
+        }
+        Ok(Item::Range(start, end))
+    }
+
+    /// Parse the items separated by `,` (or `;`) along with their text, empty items are ignored.
+    fn parse_items(&mut self) -> Result<Vec<(String, Item)>, ParseError> {
+        let mut items = Vec::new();
+        while !self.is_at_end() {
+            if self.eat(",") || self.eat(";") {
+                continue;
+            }
+
+            let start_position = self.position;
+            let item = self.parse_item()?;
+            items.push((self.input[start_position..self.position].trim().to_string(), item));
+            if !self.is_at_end() && !self.eat(",") && !self.eat(";") {
+                let rest = self.rest().trim_end();
+                return Err(self.error(self.position, rest, format!("Expected ',' before '{}'.", rest)));
+            }
+        }
+
+        // Decimal codes are most likely a mistake in a descriptor otherwise written in hexadecimal, e.g. 41 for 0x41.
+        if self.uses_hex {
+            for (word, value) in std::mem::take(&mut self.decimal_codes) {
+                let ch = match char::from_u32(value) {
+                    Some(ch) if !ch.is_control() => format!("'{}'", ch),
+                    _ => format!("U+{:04X}", value)
+                };
+                self.warn(format!("{} is decimal ({}), did you mean 0x{}?", word, ch, word));
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Suggest a hexadecimal number for a word which looks like one, e.g. `0x4A` for `4A`.
+fn hex_suggestion(word: &str) -> Option<String> {
+    let digits = word.strip_prefix(['x', 'X']).unwrap_or(word);
+    match !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        true => Some(format!("0x{}", digits)),
+        false => None
+    }
+}
+
+/// Number of single-character edits needed to turn one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_ch != *b_ch);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to the given name, if it's close enough to be a typo.
+pub(crate) fn closest_name<'n>(name: &str, candidates: impl IntoIterator<Item = &'n str>) -> Option<&'n str> {
+    let name = name.to_lowercase();
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Parse a single character code, written as `0x41`, `U+0041`, `65` or `'A'`.
+pub fn parse_single_charcode(char_code: &str) -> Result<u32, ParseError> {
+    let charsets = Charsets::new();
+    let mut parser = Parser::new(char_code, &charsets);
+    let (result, _) = parser.parse_value()?;
+    if !parser.is_at_end() {
+        let rest = parser.rest().trim_end();
+        return Err(parser.error(parser.position, rest, format!("Unexpected '{}' after the character code.", rest)));
+    }
+
+    Ok(result)
+}
+
+pub fn parse_char(char_code: u32, skip_control_characters: bool) -> Option<char> {
+    // Parse the character from u32.
+    let ch = char::from_u32(char_code)?;
 
     // Skip control characters, if needed.
     if skip_control_characters && ch.is_control() {
@@ -51,42 +389,306 @@ pub fn parse_char(char_code: u32, skip_control_characters: bool) -> Option<char>
     Some(ch)
 }
 
-pub fn char_range(descriptor: &String, skip_control_characters: bool, add_null_character: Option<bool>) -> Result<Vec<char>, ParseError> {
-    let descriptor = descriptor.replace(';', ",");
-    let mut result = Vec::<char>::new();
-
-    if add_null_character.unwrap_or_default() {
-        let ch = parse_char(0x00, false);
-        if ch.is_some() {
-            result.push(ch.unwrap());
+fn push_items(items: Vec<(String, Item)>, skip_control_characters: bool, result: &mut BTreeSet<char>) {
+    for (_, item) in items {
+        match item {
+            // A range (e.g. 0x40-0x50) or a single character code (e.g. 0x60).
+            Item::Range(start, end) => {
+                for char_code in start..end.min(CHAR_CODE_LIMIT) {
+                    if let Some(ch) = parse_char(char_code, skip_control_characters) {
+                        result.insert(ch);
+                    }
+                }
+            },
+            // Characters between quotes (e.g. "ÄÖÜ") or from a named set (e.g. script:Hiragana).
+            Item::Chars(chars) => {
+                for ch in chars {
+                    if let Some(ch) = parse_char(ch as u32, skip_control_characters) {
+                        result.insert(ch);
+                    }
+                }
+            },
+            // A charset (e.g. @latin_ext).
+            Item::Group(items) => push_items(items, skip_control_characters, result)
         }
     }
+}
 
-    for item in descriptor.split(',') {
-        let range = item.split_once('-');
+/// The character codes of an item, as end-exclusive ranges.
+fn push_intervals(item: &Item, result: &mut Vec<(u32, u32)>) {
+    match item {
+        Item::Range(start, end) => result.push((*start, (*end).min(CHAR_CODE_LIMIT))),
+        Item::Chars(chars) => result.extend(chars.iter().map(|ch| (*ch as u32, *ch as u32 + 1))),
+        Item::Group(items) => items.iter().for_each(|(_, item)| push_intervals(item, result))
+    }
+}
 
-        // The current item is a single character code (e.g. 0x60), not a range.
-        if range.is_none() {
-            let char_code = parse_single_charcode(item.trim())?;
-            let ch = parse_char(char_code, skip_control_characters);
+/// Warnings about the parts of a descriptor which are valid but most likely a mistake: reversed or empty
+/// ranges, and items overlapping each other.
+pub fn char_range_warnings(descriptor: &str, charsets: &Charsets) -> Result<Vec<String>, ParseError> {
+    let mut parser = Parser::new(descriptor, charsets);
+    let items = parser.parse_items()?;
+    let mut warnings = parser.warnings;
 
-            if ch.is_some() {
-                result.push(ch.unwrap());
+    // Sort the ranges of all the items, and compare each of them with the ranges it starts within.
+    let mut intervals = Vec::new();
+    for (index, (_, item)) in items.iter().enumerate() {
+        let mut item_intervals = Vec::new();
+        push_intervals(item, &mut item_intervals);
+        intervals.extend(item_intervals.into_iter()
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| (start, end, index)));
+    }
+    intervals.sort_unstable();
+
+    let mut overlaps = BTreeSet::new();
+    let mut active: Vec<(u32, usize)> = Vec::new();
+    for (start, end, index) in intervals {
+        active.retain(|(active_end, _)| *active_end > start);
+        for (_, other) in &active {
+            if *other != index {
+                overlaps.insert((*other.min(&index), *other.max(&index)));
             }
-            continue;
         }
+        active.push((end, index));
+    }
+
+    for (first, second) in overlaps {
+        let (first, second) = (&items[first].0, &items[second].0);
+        let message = match first == second {
+            true => format!("{} is repeated.", first),
+            false => format!("{} overlaps with {}.", first, second)
+        };
+        if !warnings.contains(&message) {
+            warnings.push(message);
+        }
+    }
+    Ok(warnings)
+}
 
-        // The current item is a range (e.g. 0x40-0x50)
-        let range_segments = range.unwrap();
-        let start = parse_single_charcode(range_segments.0)?;
-        let end = parse_single_charcode(range_segments.1)?;
+/// The characters of a descriptor, sorted and without duplicates.
+pub fn char_range(descriptor: &str, skip_control_characters: bool, add_null_character: Option<bool>) -> Result<Vec<char>, ParseError> {
+    char_range_with_charsets(descriptor, &Charsets::new(), skip_control_characters, add_null_character)
+}
 
-        for char_code in start..end {
-            let ch = parse_char(char_code, skip_control_characters);
-            if ch.is_some() {
-                result.push(ch.unwrap());
-            }
+/// Same as [`char_range`], with the given charsets available to be referenced as `@name`.
+pub fn char_range_with_charsets(descriptor: &str, charsets: &Charsets, skip_control_characters: bool, add_null_character: Option<bool>) -> Result<Vec<char>, ParseError> {
+    let mut result = BTreeSet::new();
+
+    if add_null_character.unwrap_or_default() {
+        if let Some(ch) = parse_char(0x00, false) {
+            result.insert(ch);
         }
     }
-    Ok(result)
-}
\ No newline at end of file
+
+    let items = Parser::new(descriptor, charsets).parse_items()?;
+    push_items(items, skip_control_characters, &mut result);
+    Ok(result.into_iter().collect())
+}
+
+/// Format a list of characters as `0x41-0x5b,0x61`, merging consecutive characters into ranges which include
+/// their end if `inclusive` is set.
+pub(crate) fn format_ranges(chars: &[char], inclusive: bool) -> String {
+    let mut char_codes: Vec<u32> = chars.iter().map(|ch| *ch as u32).collect();
+    char_codes.sort_unstable();
+    char_codes.dedup();
+
+    let mut items = Vec::<String>::new();
+    let mut i = 0;
+    while i < char_codes.len() {
+        let start = char_codes[i];
+        while i + 1 < char_codes.len() && char_codes[i + 1] == char_codes[i] + 1 {
+            i += 1;
+        }
+
+        let end = char_codes[i];
+        if start == end {
+            items.push(format!("0x{:x}", start));
+        } else {
+            items.push(format!("0x{:x}-0x{:x}", start, if inclusive { end } else { end + 1 }));
+        }
+        i += 1;
+    }
+    items.join(",")
+}
+
+/// Format a list of characters as a descriptor, merging consecutive characters into end-exclusive ranges.
+pub fn format_char_range(chars: &[char]) -> String {
+    format_ranges(chars, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(descriptor: &str) -> Vec<char> {
+        char_range(descriptor, false, None).unwrap()
+    }
+
+    fn codes(char_codes: impl IntoIterator<Item = u32>) -> Vec<char> {
+        char_codes.into_iter().filter_map(char::from_u32).collect()
+    }
+
+    fn warnings(descriptor: &str) -> Vec<String> {
+        char_range_warnings(descriptor, &Charsets::new()).unwrap()
+    }
+
+    fn parse_error(descriptor: &str) -> ParseError {
+        char_range(descriptor, false, None).err().unwrap()
+    }
+
+    #[test]
+    fn legacy_ranges_exclude_their_end() {
+        assert_eq!(chars("0x41-0x44"), codes([0x41, 0x42, 0x43]));
+        assert_eq!(chars("0x0,0x40-0x42,0x60"), codes([0x0, 0x40, 0x41, 0x60]));
+    }
+
+    #[test]
+    fn semicolons_and_whitespace_separate_items() {
+        assert_eq!(chars("  0x41 - 0x43 ;0x61;  0x62 ,, "), codes([0x41, 0x42, 0x61, 0x62]));
+        assert_eq!(chars(""), Vec::new());
+    }
+
+    #[test]
+    fn other_notations() {
+        assert_eq!(chars("U+0041, u+42"), codes([0x41, 0x42]));
+        assert_eq!(chars("65, 0X42"), codes([0x41, 0x42]));
+        assert_eq!(chars("\"ÄÖ\", 'Ü'"), vec!['Ä', 'Ö', 'Ü']);
+        assert_eq!(chars("'a'-'d'"), vec!['a', 'b', 'c']);
+        assert_eq!(chars("U+0041..=U+0043, 'x'..='z'"), vec!['A', 'B', 'C', 'x', 'y', 'z']);
+    }
+
+    #[test]
+    fn control_and_null_characters() {
+        assert_eq!(char_range("0x41", false, Some(true)).unwrap(), codes([0x0, 0x41]));
+        assert_eq!(char_range("0x0-0x3, 0x41", true, None).unwrap(), codes([0x41]));
+    }
+
+    #[test]
+    fn invalid_items() {
+        assert_eq!(parse_error("0x41, 4A").message, "Number 4A could not be parsed as a decimal number.");
+        assert_eq!(parse_error("0x41 0x42").message, "Expected ',' before '0x42'.");
+        assert_eq!(parse_error("'ab'-'c'").message, "\"ab\" must be a single character to be used in a range.");
+        assert_eq!(parse_error("\"abc").message, "Missing closing quote (\").");
+    }
+
+    #[test]
+    fn formatted_ranges_exclude_their_end() {
+        assert_eq!(format_char_range(&codes([0x43, 0x0, 0x41, 0x42, 0x61])), "0x0,0x41-0x44,0x61");
+        assert_eq!(chars(&format_char_range(&codes([0x41, 0x42, 0x43]))), codes([0x41, 0x42, 0x43]));
+    }
+
+    #[test]
+    fn named_sets() {
+        assert_eq!(chars("block:Basic Latin").len(), 0x80 - 1);
+        assert_eq!(chars("block:latin_1_SUPPLEMENT").first(), Some(&'\u{80}'));
+        assert_eq!(chars("script:Hira"), chars("script:Hiragana"));
+        assert!(chars("script:Hiragana").contains(&'あ'));
+        assert!(chars("category:Lu").contains(&'A') && !chars("category:Lu").contains(&'a'));
+        assert!(chars("category:L").contains(&'a'));
+        assert_eq!(chars("category:lu"), chars("category:Lu"));
+        assert_eq!(chars("category: n"), chars("category:N"));
+    }
+
+    #[test]
+    fn unknown_named_sets() {
+        let error = parse_error("0x41, script:Klingon, 0x42");
+        assert_eq!((error.input.as_str(), error.message.as_str()), ("Klingon", "Unknown Unicode script 'Klingon'."));
+        assert_eq!(parse_error("block:Basic Latn").message, "Unknown Unicode block 'Basic Latn'.");
+    }
+
+    #[test]
+    fn charsets() {
+        let charsets = Charsets::from([
+            ("digits".to_string(), "'0'..='9'".to_string()),
+            ("hex".to_string(), "@digits, 'a'..='f'".to_string())
+        ]);
+        let chars = char_range_with_charsets("@hex, 'x'", &charsets, false, None).unwrap();
+        assert_eq!(chars.iter().collect::<String>(), "0123456789abcdefx");
+    }
+
+    #[test]
+    fn errors_in_charsets() {
+        let charsets = Charsets::from([
+            ("latin".to_string(), "0x20-0x7f, zz".to_string()),
+            ("loop".to_string(), "@loop".to_string())
+        ]);
+        let error = char_range_with_charsets("@latin", &charsets, false, None).err().unwrap();
+        assert!(error.message.starts_with("In charset '@latin': "));
+
+        let error = char_range_with_charsets("@loop", &charsets, false, None).err().unwrap();
+        assert_eq!(error.message, "In charset '@loop': Charset '@loop' references itself.");
+
+        let error = char_range_with_charsets("@greek", &charsets, false, None).err().unwrap();
+        assert_eq!((error.input.as_str(), error.message.as_str()), ("@greek", "Unknown charset '@greek'."));
+    }
+
+    #[test]
+    fn chars_are_sorted_without_duplicates_or_spaces() {
+        assert_eq!(chars("0x43, 0x41-0x44, 'B', ' '"), codes([0x41, 0x42, 0x43]));
+    }
+
+    #[test]
+    fn reversed_and_empty_ranges() {
+        assert_eq!(chars("0x44-0x41"), Vec::new());
+        assert_eq!(warnings("0x44-0x41"), ["Range 0x44-0x41 is reversed and does not contain any character."]);
+        assert_eq!(warnings("0x41-0x41"), ["Range 0x41-0x41 is empty, the end of a range using '-' is excluded."]);
+        assert!(warnings("0x41..=0x41").is_empty());
+    }
+
+    #[test]
+    fn overlapping_items() {
+        assert_eq!(warnings("0x41-0x50, 'C'"), ["0x41-0x50 overlaps with 'C'."]);
+        assert_eq!(warnings("0x41, 0x41"), ["0x41 is repeated."]);
+        assert!(warnings("0x41-0x50, 0x50-0x60").is_empty());
+    }
+
+    #[test]
+    fn mixed_notations_in_a_range() {
+        assert_eq!(warnings("0x20-100"), ["Range 0x20-100 mixes hexadecimal and decimal numbers, did you mean 0x20-0x100?"]);
+        assert_eq!(parse_error("0x20-7f").suggestion.as_deref(), Some("0x7f"));
+        assert!(warnings("32-127").is_empty());
+    }
+
+    #[test]
+    fn decimal_codes_among_hexadecimal_ones() {
+        assert_eq!(warnings("0x41-0x5b, 41"), ["41 is decimal (')'), did you mean 0x41?"]);
+        assert_eq!(warnings("U+0020, 10-20"), ["10 is decimal (U+000A), did you mean 0x10?", "20 is decimal (U+0014), did you mean 0x20?"]);
+        assert!(warnings("41").is_empty());
+        assert!(warnings("0x41, 'B'").is_empty());
+    }
+
+    #[test]
+    fn errors_point_at_the_token() {
+        let error = parse_error("0x41, 4A");
+        assert_eq!((error.offset, error.input.as_str()), (6, "4A"));
+        assert_eq!(error.suggestion.as_deref(), Some("0x4A"));
+        assert_eq!(error.to_string(), "Number 4A could not be parsed as a decimal number. Did you mean 0x4A?");
+
+        let error = parse_error("\"Ä\", zz");
+        assert_eq!((error.offset, error.column("\"Ä\", zz")), (6, 6));
+
+        let error = parse_error("0x41 0x42");
+        assert_eq!(error.offset, 5);
+    }
+
+    #[test]
+    fn unknown_names_are_suggested() {
+        let error = parse_error("block:Basic Latn");
+        assert_eq!((error.offset, error.input.as_str()), (6, "Basic Latn"));
+        assert_eq!(error.suggestion.as_deref(), Some("block:Basic Latin"));
+        assert_eq!(parse_error("category:Lz").suggestion.as_deref(), Some("category:L"));
+        assert_eq!(parse_error("category:Xx").suggestion, None);
+
+        let charsets = Charsets::from([("digits".to_string(), "'0'..='9'".to_string())]);
+        let error = char_range_with_charsets("@digit", &charsets, false, None).err().unwrap();
+        assert_eq!(error.suggestion.as_deref(), Some("@digits"));
+    }
+
+    #[test]
+    fn errors_in_charsets_point_into_the_charset() {
+        let charsets = Charsets::from([("latin".to_string(), "0x20-0x7f, zz".to_string())]);
+        let error = char_range_with_charsets("0x41, @latin", &charsets, false, None).err().unwrap();
+        assert_eq!((error.offset, error.charset.as_deref()), (11, Some("latin")));
+    }
+}
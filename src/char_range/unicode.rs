@@ -0,0 +1,125 @@
+use std::sync::OnceLock;
+use unicode_blocks::{find_unicode_block, UnicodeBlock};
+use unicode_general_category::get_general_category;
+use unicode_script::Script;
+
+use super::CHAR_CODE_LIMIT;
+
+/// Consecutive characters sharing the same value of a property, from `start` (inclusive) to `end` (exclusive).
+struct Run<T> {
+    start: u32,
+    end: u32,
+    value: T
+}
+
+/// Compare names the way Unicode recommends for property values: ignoring case, spaces, hyphens and
+/// underscores, so that `Latin-1 Supplement` also matches `latin_1_supplement`.
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|ch| !ch.is_whitespace() && *ch != '-' && *ch != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Split all the characters into runs of the given property, skipping the surrogates which are not characters.
+fn runs<T: Copy + PartialEq>(property: impl Fn(char) -> T) -> Vec<Run<T>> {
+    let mut runs: Vec<Run<T>> = Vec::new();
+    for ch in (0..CHAR_CODE_LIMIT).filter_map(char::from_u32) {
+        let value = property(ch);
+        match runs.last_mut() {
+            Some(run) if run.end == ch as u32 && run.value == value => run.end += 1,
+            _ => runs.push(Run { start: ch as u32, end: ch as u32 + 1, value })
+        }
+    }
+    runs
+}
+
+/// The characters of the runs whose value is accepted by the given function.
+fn chars_of<T>(runs: &[Run<T>], accept: impl Fn(&T) -> bool) -> Vec<char> {
+    runs.iter()
+        .filter(|run| accept(&run.value))
+        .flat_map(|run| (run.start..run.end).filter_map(char::from_u32))
+        .collect()
+}
+
+// Looking up a property scans every character, so the tables are only built once.
+fn blocks() -> &'static [UnicodeBlock] {
+    static BLOCKS: OnceLock<Vec<UnicodeBlock>> = OnceLock::new();
+    BLOCKS.get_or_init(|| {
+        let mut blocks = Vec::new();
+        let mut char_code = 0;
+        while char_code < CHAR_CODE_LIMIT {
+            match char::from_u32(char_code).and_then(find_unicode_block) {
+                Some(block) => {
+                    blocks.push(block);
+                    char_code = block.end() + 1;
+                },
+                // Blocks always start at a multiple of 16 code points.
+                None => char_code += 16
+            }
+        }
+        blocks
+    })
+}
+
+fn scripts() -> &'static [Run<Script>] {
+    static SCRIPTS: OnceLock<Vec<Run<Script>>> = OnceLock::new();
+    SCRIPTS.get_or_init(|| runs(Script::from))
+}
+
+fn categories() -> &'static [Run<&'static str>] {
+    static CATEGORIES: OnceLock<Vec<Run<&'static str>>> = OnceLock::new();
+    CATEGORIES.get_or_init(|| runs(|ch| get_general_category(ch).abbreviation()))
+}
+
+/// Find the range of the block with the given name (e.g. `Latin-1 Supplement`), end-exclusive.
+pub fn block(name: &str) -> Option<(u32, u32)> {
+    let name = normalize_name(name);
+    blocks().iter()
+        .find(|block| normalize_name(block.name()) == name)
+        .map(|block| (block.start(), block.end() + 1))
+}
+
+pub fn block_names() -> Vec<&'static str> {
+    blocks().iter().map(|block| block.name()).collect()
+}
+
+pub fn script_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = scripts().iter().map(|run| run.value.full_name()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Find the characters of the script with the given full (e.g. `Hiragana`) or short (e.g. `Hira`) name.
+pub fn script(name: &str) -> Option<Vec<char>> {
+    let name = normalize_name(name);
+    let script = scripts().iter()
+        .map(|run| run.value)
+        .find(|script| normalize_name(script.full_name()) == name || normalize_name(script.short_name()) == name)?;
+
+    Some(chars_of(scripts(), |value| *value == script))
+}
+
+pub fn category_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = categories().iter().map(|run| run.value).collect();
+    names.extend(names.iter().map(|name| &name[..1]).collect::<Vec<&'static str>>());
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Find the characters of the general category with the given abbreviation (e.g. `Lu`), or of all the
+/// categories of a major class (e.g. `L`).
+pub fn category(name: &str) -> Option<Vec<char>> {
+    let name = normalize_name(name);
+    let chars = chars_of(categories(), |abbreviation| {
+        let abbreviation = normalize_name(abbreviation);
+        abbreviation == name || (name.len() == 1 && abbreviation.starts_with(&name))
+    });
+
+    match chars.is_empty() {
+        true => None,
+        false => Some(chars)
+    }
+}
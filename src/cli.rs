@@ -0,0 +1,168 @@
+use std::{fmt::Display, num::NonZeroUsize, path::PathBuf};
+use clap::{Args, Parser, Subcommand};
+use indicatif::{MultiProgress, ProgressDrawTarget};
+
+#[derive(Parser)]
+#[command(
+    name = "ejf-utils",
+    version,
+    about = "Generates .ejf font files for the MicroUI embedded platform.",
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Same as the build command, kept for compatibility.
+    #[command(flatten)]
+    pub build: BuildArgs,
+
+    /// Only print errors.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Print the results as JSON instead of displaying progress bars.
+    #[arg(long, global = true)]
+    pub json: bool
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Generate the fonts declared in a manifest.
+    Build(BuildArgs),
+
+    /// Rebuild the fonts in memory and check that they are identical to the ones on disk.
+    Verify(ManifestArgs),
+
+    /// Regenerate the fonts whenever the manifest or one of the input fonts changes.
+    Watch(ManifestArgs),
+
+    /// Display information about an existing .ejf file.
+    Inspect {
+        /// Path to the .ejf file.
+        file: PathBuf
+    },
+
+    /// Write the fonts declared in a manifest as the entries of a MicroEJ .fonts.list file.
+    ExportFontsList(ExportArgs),
+
+    /// Convert a MicroEJ .fonts.list file into the fonts of a manifest.
+    ImportFontsList(ImportArgs)
+}
+
+impl Command {
+    /// Whether the command prints its result on the standard output, which must then only contain the result.
+    pub fn prints_result(&self) -> bool {
+        match self {
+            Command::ExportFontsList(args) => args.output.is_none(),
+            Command::ImportFontsList(args) => args.output.is_none(),
+            _ => false
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ManifestArgs {
+    /// Path to the manifest (.toml) declaring the fonts.
+    #[arg(required = true)]
+    pub manifest: Option<String>,
+
+    /// Only process the fonts with the given name (the file name of the output, without extension).
+    #[arg(long, value_name = "NAME")]
+    pub only: Vec<String>,
+
+    /// Write the fonts to the given directory instead of the one in their output path.
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Number of fonts processed at the same time, defaults to the number of CPUs.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>
+}
+
+#[derive(Args)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub manifest: ManifestArgs,
+
+    /// Rebuild all the fonts, even the ones that are up to date.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Write a JSON report with the statistics of every font to the given file.
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Path to the manifest (.toml) declaring the fonts.
+    pub manifest: String,
+
+    /// Only export the fonts with the given name (the file name of the output, without extension).
+    #[arg(long, value_name = "NAME")]
+    pub only: Vec<String>,
+
+    /// Directory the paths of the fonts are relative to, usually the resources of the application. Defaults
+    /// to the directory of the manifest.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Write the .fonts.list to the given file instead of the standard output.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Path to the .fonts.list file.
+    pub file: PathBuf,
+
+    /// Directory the paths of the .fonts.list are relative to, usually the resources of the application.
+    /// Defaults to the directory of the .fonts.list.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Write the manifest to the given file instead of the standard output.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>
+}
+
+/// Determines what gets printed, depending on the `--quiet` and `--json` flags.
+#[derive(Clone, Copy)]
+pub struct Output {
+    pub quiet: bool,
+    pub json: bool
+}
+
+impl Output {
+    /// Whether progress and informative messages are displayed.
+    pub fn is_interactive(&self) -> bool {
+        !self.quiet && !self.json
+    }
+
+    pub fn message(&self, message: impl Display) {
+        if self.is_interactive() {
+            println!("{}", message);
+        }
+    }
+
+    /// Warnings are displayed on the standard error, unless `--quiet` is given.
+    pub fn warning(&self, message: impl Display) {
+        if !self.quiet {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Errors are always displayed, on the standard error so that they don't interfere with JSON output.
+    pub fn error(&self, message: impl Display) {
+        eprintln!("{}", message);
+    }
+
+    pub fn progress(&self) -> MultiProgress {
+        match self.is_interactive() {
+            true => MultiProgress::new(),
+            false => MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        }
+    }
+}
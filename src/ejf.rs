@@ -1,144 +1,289 @@
-use zip::{ZipWriter, write::FileOptions, CompressionMethod};
-use freetype::{Library, Face};
-use image::ImageFormat;
+use zip::{ZipArchive, CompressionMethod};
 use serde::{Serialize, Deserialize};
-use self::{header::HeaderInfo, metrics::determine_metrics_from_font, renderer::RenderConfig};
 
-use super::char_range;
+use crate::{char_range::{char_range_with_charsets, parse_char, Charsets}, extract};
 
+mod builder;
 mod errors;
 mod header;
+mod kerning;
 mod renderer;
 mod metrics;
 
-use std::{fs::File, io::{Write, Cursor}, path::Path};
+use std::{collections::BTreeSet, fmt, fs::File, io::{Read, Write, Seek}, path::Path};
+pub use crate::ejf::builder::{FontBuilder, FontSource};
 pub use crate::ejf::errors::Error;
+pub use crate::ejf::kerning::{write_kerning, KerningFormat, KerningPair};
 
+const DEFAULT_SIZE: u32 = 12;
 const DEFAULT_DPI: u32 = 72;
 const DEFAULT_LEFT_SPACING: u8 = 0;
 const DEFAULT_RIGHT_SPACING: u8 = 1;
-const PRINT_CHARACTERS: bool = false;
+const ENTRY_PERMISSIONS: u32 = 0o644;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Compression method used for the entries of the .ejf (ZIP) archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Stored,
+    Deflate
+}
+
+impl From<Compression> for CompressionMethod {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Stored => CompressionMethod::Stored,
+            Compression::Deflate => CompressionMethod::Deflated
+        }
+    }
+}
+
+/// What the `size` of a font is measured in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeUnit {
+    /// Points, at the resolution given by `dpi`.
+    #[default]
+    Pt,
+    /// Pixels per em, whatever the resolution.
+    Px,
+    /// Height of the capital letters (that of `H`) in pixels.
+    CapHeightPx,
+    /// Height of the font, that is of its glyphs, in pixels.
+    LineHeightPx
+}
+
+impl fmt::Display for SizeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeUnit::Pt => write!(f, "size in points"),
+            SizeUnit::Px => write!(f, "size in pixels"),
+            SizeUnit::CapHeightPx => write!(f, "cap height"),
+            SizeUnit::LineHeightPx => write!(f, "line height")
+        }
+    }
+}
+
+/// What the glyph of U+0000 looks like. MicroUI displays the first glyph of a font in place of the characters
+/// missing from it, which is U+0000 when it's part of the font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fallback {
+    /// A blank glyph, 1px wide unless another width is given.
+    Invisible,
+    /// The outline of a box, as wide as half the height of the font unless another width is given.
+    Box,
+    /// The replacement character (U+FFFD) of the input font.
+    Replacement,
+    /// A custom image, drawn in black on white like the other glyphs.
+    Image
+}
+
+/// A font as declared in a `[[font]]` section of a manifest.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EjfConfig {
     pub input: String,
     pub output: String,
     pub size: u32,
+    /// What `size` is measured in, points by default.
+    pub size_unit: Option<SizeUnit>,
+    #[serde(default)]
     pub char_range: String,
+    /// Glob patterns of the source and translation files whose characters are added to `char_range`.
+    pub chars_from: Option<Vec<String>>,
     pub ignore_char_range: Option<String>,
     pub skip_control_characters: bool,
     pub add_null_character: Option<bool>,
+    /// The glyph of U+0000, which is added to the font when this is set.
+    pub fallback: Option<Fallback>,
+    /// Width of the `invisible` and `box` fallback glyphs, in pixels.
+    pub fallback_width: Option<u32>,
+    /// Path to the image of the `image` fallback glyph.
+    pub fallback_image: Option<String>,
+    /// Characters given the same width: `"digits"`, `"all"` or a range such as `"0x30-0x3a, ':'"`.
+    pub monospace: Option<String>,
+    /// Width of the monospaced characters in pixels, instead of the width of the widest of them.
+    pub monospace_width: Option<u32>,
+    /// Embed the space (U+0020) as a glyph, instead of only writing its width in the header.
+    pub embed_space: Option<bool>,
+    /// Width of the space in pixels, instead of the one of the input font.
+    pub space_width: Option<u32>,
+    /// OpenType features applied when choosing the glyph of a character, e.g. `["tnum", "zero"]`.
+    pub features: Option<Vec<String>>,
+    /// Path of the file the kerning pairs of the characters are written to, in the format given by its extension.
+    pub kerning: Option<String>,
     pub dpi: Option<u32>,
     pub left_spacing: Option<u8>,
-    pub right_spacing: Option<u8>
+    pub right_spacing: Option<u8>,
+    pub compression: Option<Compression>,
+    pub compression_level: Option<i32>,
+    /// Number of bits per pixel the MicroEJ SDK converts the font to, only used for the `.fonts.list`.
+    pub bpp: Option<u8>,
+    /// The charsets defined in the manifest, which `char_range`, `ignore_char_range` and `monospace` can reference. Not
+    /// serialized, the characters they resolve to are part of the lockfile fingerprint instead.
+    #[serde(skip)]
+    pub charsets: Charsets
+}
+
+impl EjfConfig {
+    /// A font generated from the given input at the given size, with the default value of every other option.
+    pub fn new(input: &str, output: &str, size: u32) -> EjfConfig {
+        EjfConfig {
+            input: input.to_string(),
+            output: output.to_string(),
+            size,
+            size_unit: None,
+            char_range: String::new(),
+            chars_from: None,
+            ignore_char_range: None,
+            skip_control_characters: false,
+            add_null_character: None,
+            fallback: None,
+            fallback_width: None,
+            fallback_image: None,
+            monospace: None,
+            monospace_width: None,
+            embed_space: None,
+            space_width: None,
+            features: None,
+            kerning: None,
+            dpi: None,
+            left_spacing: None,
+            right_spacing: None,
+            compression: None,
+            compression_level: None,
+            bpp: None,
+            charsets: Charsets::new()
+        }
+    }
 }
 
+/// Statistics about a font that was just built.
+#[non_exhaustive]
 pub struct EjfResult {
     pub height: u32,
     pub name: String,
-    pub space_width: u32
+    pub space_width: u32,
+    /// Distance from the top of the characters to the baseline, in pixels.
+    pub baseline: u32,
+    pub glyph_count: usize,
+    /// Characters that are not present in the input font, and thus rendered as its missing glyph.
+    pub missing_glyphs: Vec<char>,
+    /// Characters that did not entirely fit in the height of the font and were cut off.
+    pub clipped_glyphs: Vec<char>,
+    /// Size of the resulting .ejf, in bytes.
+    pub archive_size: u64
+}
+
+/// Information about an existing .ejf file, as read back from its archive.
+#[non_exhaustive]
+pub struct EjfInfo {
+    pub name: String,
+    pub height: u32,
+    pub space_width: u32,
+    /// Baseline written in the header, always the one of the original generator.
+    pub baseline: u32,
+    pub chars: Vec<char>,
+    pub entries: usize,
+    /// Compression method of the header, `None` if it's not one that can be configured.
+    pub compression: Option<Compression>
 }
 
 /// Determine font name (same as the path, minus extension).
-pub fn get_font_name(output_name: &String) -> Result<String, Error> {
+pub fn get_font_name(output_name: &str) -> Result<String, Error> {
     let path = Path::new(output_name);
     match path.file_stem() {
         Some(path) => Ok(path.to_string_lossy().to_string()),
-        None => Err(Error::NameError)
+        None => Err(Error::NameError { path: output_name.to_string() })
     }
 }
 
+/// The characters of the font, sorted and without duplicates.
 pub fn build_char_list(config: &EjfConfig) -> Result<Vec<char>, Error> {
-    let all_chars = char_range(&config.char_range, config.skip_control_characters, config.add_null_character)?;
-    let ignored_chars = if config.ignore_char_range.is_some() {
-        char_range(&config.ignore_char_range.clone().unwrap(), false, Some(false))?
-    } else { Vec::new() };
-
-    let mut result: Vec<char> = Vec::new();
-    for ch in all_chars {
-        if !ignored_chars.contains(&ch) {
-            result.push(ch);
+    let add_null_character = config.add_null_character.unwrap_or_default() || config.fallback.is_some();
+    let mut all_chars: BTreeSet<char> = char_range_with_charsets(&config.char_range, &config.charsets, config.skip_control_characters, Some(add_null_character))?
+        .into_iter()
+        .collect();
+
+    if config.embed_space.unwrap_or_default() {
+        all_chars.insert(' ');
+    }
+
+    // Add the characters used in the files of chars_from. The control characters of the text, such as line
+    // breaks and tabs, are never displayed, whatever skip_control_characters says about the ranges.
+    if let Some(patterns) = &config.chars_from {
+        all_chars.extend(extract::chars_from(patterns)?.into_iter()
+            .filter_map(|ch| parse_char(ch as u32, true)));
+    }
+
+    if let Some(ignore_char_range) = &config.ignore_char_range {
+        for ch in char_range_with_charsets(ignore_char_range, &config.charsets, false, Some(false))? {
+            all_chars.remove(&ch);
         }
     }
 
-    Ok(result)
+    Ok(all_chars.into_iter().collect())
 }
 
+/// The characters of the font given the same width by `monospace`, from the characters of the font.
+pub fn build_monospace_list(config: &EjfConfig, chars: &[char]) -> Result<Vec<char>, Error> {
+    Ok(match config.monospace.as_deref() {
+        None => Vec::new(),
+        Some("all") => chars.to_vec(),
+        Some("digits") => ('0'..='9').collect(),
+        Some(descriptor) => char_range_with_charsets(descriptor, &config.charsets, false, None)?
+    })
+}
+
+/// Build the .ejf described by the config and write it to the output file, along with its kerning file if any.
 pub fn build_ejf<F>(config: &EjfConfig, progress_callback: F) -> Result<EjfResult, Error>
     where F: Fn((i32, i32))
 {
-    let font_name = get_font_name(&config.output)?;
-
-    // Parse the character range from the config.
-    let chars = build_char_list(&config)?;
-
-    // Open the output file
-    let zip_file = File::create(&config.output)?;
-    let mut zip = ZipWriter::new(zip_file);
-
-    // Try to open the font.
-    let library = Library::init()?;
-    let face: Face = library.new_face(&config.input, 0)?;
-
-    // Set face properties.
-    let char_width = config.size as isize * 64;
-    let dpi = config.dpi.unwrap_or(DEFAULT_DPI);
-    face.set_char_size(char_width, 0, dpi, 0)?;
-    
-    // Determine max height.
-    let metrics = determine_metrics_from_font(&face)?;
-    let image_height = metrics.height;
-
-    // Render the characters.
-    let zip_options = FileOptions::default()
-        .compression_method(CompressionMethod::Stored);
-    let render_config = RenderConfig {
-        left_spacing: config.left_spacing.unwrap_or(DEFAULT_LEFT_SPACING),
-        right_spacing: config.right_spacing.unwrap_or(DEFAULT_RIGHT_SPACING),
-        max_ascent: metrics.ascent,
-        total_height: image_height
-    };
+    let builder = FontBuilder::from_config(config)?;
+    let zip_file = File::create(&config.output)
+        .map_err(|e| Error::io(&config.output, e))?;
+    let result = builder.write(zip_file, &get_font_name(&config.output)?, &config.output, progress_callback)?;
+    if let Some(kerning) = &config.kerning {
+        builder.build_kerning_to_file(kerning)?;
+    }
+    Ok(result)
+}
+
+/// Build the .ejf described by the config and write it to the given writer instead of the output file.
+pub fn write_ejf<W, F>(config: &EjfConfig, writer: W, progress_callback: F) -> Result<EjfResult, Error>
+    where W: Write + Seek, F: Fn((i32, i32))
+{
+    let builder = FontBuilder::from_config(config)?;
+    builder.write(writer, &get_font_name(&config.output)?, &config.output, progress_callback)
+}
 
-    let mut num_processed = 0;
-    for ch in &chars {
-        let image = renderer::render_single_character(&face, *ch, &render_config);
-        let mut cursor = Cursor::new(Vec::new());
+/// Read the header of an existing .ejf file.
+pub fn read_ejf<R>(reader: R) -> Result<EjfInfo, Error>
+    where R: Read + Seek
+{
+    let mut zip = ZipArchive::new(reader).map_err(Error::ZipReaderError)?;
+    let entries = zip.len();
+    let mut header_file = zip.by_name("Header").map_err(Error::ZipReaderError)?;
+    let compression = match header_file.compression() {
+        CompressionMethod::Stored => Some(Compression::Stored),
+        CompressionMethod::Deflated => Some(Compression::Deflate),
+        _ => None
+    };
 
-        if PRINT_CHARACTERS {
-            renderer::print_character(&image);
-        }
+    let mut header_data = Vec::new();
+    header_file.read_to_end(&mut header_data).map_err(|e| Error::ZipReaderError(e.into()))?;
+    let header = header::read_header(&header_data).map_err(Error::XmlReaderError)?
+        .ok_or(Error::InvalidHeader)?;
 
-        image.to_rgb8().write_to(&mut cursor, ImageFormat::Png)?;
-        
-        // Write the character to the zip file
-        let char_code = format!("0x{:x}", *ch as u32);
-        let image_data = cursor.into_inner();
-        zip.start_file(&char_code, zip_options)?;
-        zip.write(&image_data)?;        
-
-        // Also write the "design" character to the zip file.
-        zip.start_file(format!("design_{}", &char_code), zip_options)?;
-        zip.write(&image_data)?;
-
-        num_processed += 1;
-        progress_callback((num_processed, chars.len() as i32));
-    }    
-
-    // Write the header
-    let space_width = renderer::get_char_width(&face, ' ', &render_config);
-    let header = header::write_header(HeaderInfo {
-        chars: chars,
-        height: image_height,
-        name: font_name.to_string(),
-        space_width
-    })?;
-    zip.start_file("Header", zip_options)?;
-    zip.write(&header)?;
-    zip.finish()?;
-
-    Ok(EjfResult {
-        height: image_height,
-        name: font_name.to_string(),
-        space_width
+    Ok(EjfInfo {
+        name: header.name,
+        height: header.height,
+        space_width: header.space_width,
+        baseline: header.baseline,
+        chars: header.chars,
+        entries,
+        compression
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,747 @@
+use std::{
+    cmp::max, collections::{BTreeSet, HashMap}, fmt, fs::{self, File}, io::{Cursor, Seek, Write},
+    panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}, mpsc}, thread
+};
+use freetype::{face::LoadFlag, Face, Library};
+use image::{GrayImage, ImageFormat, Luma, LumaA};
+use rustybuzz::{Feature, UnicodeBuffer};
+use zip::{ZipWriter, write::FileOptions, DateTime};
+
+use super::{
+    header::{self, HeaderInfo},
+    metrics::determine_metrics_from_font,
+    kerning::{self, KerningFormat, KerningPair},
+    renderer::{self, FallbackGlyph, RenderConfig},
+    build_char_list, build_monospace_list, get_font_name, Compression, EjfConfig, EjfResult, Error, Fallback, SizeUnit,
+    DEFAULT_DPI, DEFAULT_LEFT_SPACING, DEFAULT_RIGHT_SPACING, DEFAULT_SIZE, ENTRY_PERMISSIONS
+};
+
+type ProgressCallback = Arc<dyn Fn((i32, i32)) + Send + Sync>;
+
+/// The TrueType/OpenType font a .ejf is generated from.
+#[derive(Debug, Clone)]
+pub enum FontSource {
+    /// Path to a font file.
+    File(String),
+    /// Content of a font file, already loaded in memory.
+    Memory(Arc<Vec<u8>>)
+}
+
+impl FontSource {
+    /// Read the content of the font, so that it can be opened by every rendering thread.
+    fn read(&self) -> Result<Arc<Vec<u8>>, Error> {
+        match self {
+            FontSource::File(path) => fs::read(path).map(Arc::new).map_err(|e| Error::io(path, e)),
+            FontSource::Memory(data) => Ok(data.clone())
+        }
+    }
+}
+
+/// Resolution at which a size in points is the same in pixels.
+const PIXEL_DPI: u32 = 72;
+
+/// The size of a face, in 26.6 fractional points at the given resolution.
+#[derive(Clone, Copy)]
+struct FaceSize {
+    char_width: isize,
+    dpi: u32
+}
+
+/// Height of the capital letters, that is of `H`, above the baseline.
+fn cap_height<B>(face: &Face<B>) -> Result<u32, Error> {
+    if face.get_char_index('H' as usize) == 0 {
+        return Err(Error::MetricsError);
+    }
+    face.load_char('H' as usize, LoadFlag::RENDER)
+        .map_err(|source| Error::GlyphLoadError { ch: 'H', source })?;
+    Ok(max(0, face.glyph().bitmap_top()) as u32)
+}
+
+/// A character rendered by one of the threads, ready to be written to the archive.
+struct RenderedChar {
+    image_data: Vec<u8>,
+    missing: bool,
+    clipped: bool
+}
+
+/// Render a character, centered in an image of the given width for the monospaced ones.
+fn render_char<B>(face: &Face<B>, ch: char, render_config: &RenderConfig, width: Option<u32>) -> Result<RenderedChar, Error> {
+    let mut glyph = renderer::render_single_character(face, ch, render_config)?;
+    if let Some(width) = width {
+        glyph = renderer::fit_width(glyph, width);
+    }
+    #[cfg(feature = "print-characters")]
+    renderer::print_character(&glyph.image);
+
+    let mut cursor = Cursor::new(Vec::new());
+    glyph.image.to_rgb8().write_to(&mut cursor, ImageFormat::Png)
+        .map_err(|source| Error::ImageError { ch, source })?;
+
+    Ok(RenderedChar {
+        image_data: cursor.into_inner(),
+        // The NULL character is never part of the font, it's only added to be used as a fallback.
+        missing: match ch {
+            '\0' => matches!(render_config.fallback, Some(FallbackGlyph::Replacement)) && face.get_char_index(0xFFFD) == 0,
+            _ => face.get_char_index(ch as usize) == 0
+        },
+        clipped: glyph.clipped
+    })
+}
+
+impl fmt::Display for FontSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontSource::File(path) => write!(f, "{}", path),
+            FontSource::Memory(data) => write!(f, "<{} bytes in memory>", data.len())
+        }
+    }
+}
+
+impl From<String> for FontSource {
+    fn from(path: String) -> Self {
+        FontSource::File(path)
+    }
+}
+
+impl From<&str> for FontSource {
+    fn from(path: &str) -> Self {
+        FontSource::File(path.to_string())
+    }
+}
+
+impl From<PathBuf> for FontSource {
+    fn from(path: PathBuf) -> Self {
+        FontSource::File(path.to_string_lossy().to_string())
+    }
+}
+
+impl From<&Path> for FontSource {
+    fn from(path: &Path) -> Self {
+        FontSource::File(path.to_string_lossy().to_string())
+    }
+}
+
+impl From<Vec<u8>> for FontSource {
+    fn from(data: Vec<u8>) -> Self {
+        FontSource::Memory(Arc::new(data))
+    }
+}
+
+impl From<&[u8]> for FontSource {
+    fn from(data: &[u8]) -> Self {
+        FontSource::Memory(Arc::new(data.to_vec()))
+    }
+}
+
+/// Builds a single .ejf font from a TrueType/OpenType font, given either as a path or as its content
+/// (see [`FontSource`]).
+///
+/// ```no_run
+/// use ejf_utils::{FontBuilder, char_range::char_range};
+///
+/// let result = FontBuilder::new("fonts/Roboto-Regular.ttf")
+///     .size(16)
+///     .chars(char_range("0x20-0x7f", true, None)?)
+///     .build_to_file("output/Roboto16.ejf")?;
+/// println!("{} glyphs, {}px high", result.glyph_count, result.height);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// Fonts can also be built entirely in memory:
+///
+/// ```no_run
+/// # use ejf_utils::FontBuilder;
+/// # let font_data: Vec<u8> = Vec::new();
+/// let mut ejf = std::io::Cursor::new(Vec::new());
+/// FontBuilder::new(font_data)
+///     .name("Roboto16")
+///     .size(16)
+///     .chars('a'..='z')
+///     .build_to_writer(&mut ejf)?;
+/// # Ok::<(), ejf_utils::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct FontBuilder {
+    input: FontSource,
+    name: Option<String>,
+    size: u32,
+    size_unit: SizeUnit,
+    chars: Vec<char>,
+    dpi: u32,
+    left_spacing: u8,
+    right_spacing: u8,
+    compression: Compression,
+    compression_level: Option<i32>,
+    space_width: Option<u32>,
+    fallback: Option<Fallback>,
+    fallback_width: Option<u32>,
+    fallback_image: Option<String>,
+    monospace: BTreeSet<char>,
+    monospace_width: Option<u32>,
+    features: Vec<String>,
+    threads: Option<usize>,
+    progress: Option<ProgressCallback>
+}
+
+impl FontBuilder {
+    /// Start building a font from the given font file path or font data.
+    pub fn new(input: impl Into<FontSource>) -> FontBuilder {
+        FontBuilder {
+            input: input.into(),
+            name: None,
+            size: DEFAULT_SIZE,
+            size_unit: SizeUnit::default(),
+            chars: Vec::new(),
+            dpi: DEFAULT_DPI,
+            left_spacing: DEFAULT_LEFT_SPACING,
+            right_spacing: DEFAULT_RIGHT_SPACING,
+            compression: Compression::default(),
+            compression_level: None,
+            space_width: None,
+            fallback: None,
+            fallback_width: None,
+            fallback_image: None,
+            monospace: BTreeSet::new(),
+            monospace_width: None,
+            features: Vec::new(),
+            threads: None,
+            progress: None
+        }
+    }
+
+    /// Start building the font described by an entry of a manifest.
+    pub fn from_config(config: &EjfConfig) -> Result<FontBuilder, Error> {
+        let chars = build_char_list(config)?;
+        let monospace = build_monospace_list(config, &chars)?;
+
+        let mut builder = FontBuilder::new(config.input.clone())
+            .name(get_font_name(&config.output)?)
+            .size(config.size)
+            .size_unit(config.size_unit.unwrap_or_default())
+            .chars(chars)
+            .monospace(monospace)
+            .dpi(config.dpi.unwrap_or(DEFAULT_DPI))
+            .left_spacing(config.left_spacing.unwrap_or(DEFAULT_LEFT_SPACING))
+            .right_spacing(config.right_spacing.unwrap_or(DEFAULT_RIGHT_SPACING))
+            .compression(config.compression.unwrap_or_default());
+        builder.compression_level = config.compression_level;
+        builder.space_width = config.space_width;
+        builder.fallback = config.fallback;
+        builder.fallback_width = config.fallback_width;
+        builder.fallback_image = config.fallback_image.clone();
+        builder.monospace_width = config.monospace_width;
+        builder.features = config.features.clone().unwrap_or_default();
+        Ok(builder)
+    }
+
+    /// Name of the font written in its header. Defaults to the file name (without extension) of the output
+    /// when building to a file, or of the input otherwise. Required when building from font data to a writer.
+    pub fn name(mut self, name: impl Into<String>) -> FontBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Size of the font, in points unless another [unit](Self::size_unit) is given.
+    pub fn size(mut self, size: u32) -> FontBuilder {
+        self.size = size;
+        self
+    }
+
+    /// What the [size](Self::size) is measured in. Sizes in pixels do not depend on the [resolution](Self::dpi).
+    pub fn size_unit(mut self, size_unit: SizeUnit) -> FontBuilder {
+        self.size_unit = size_unit;
+        self
+    }
+
+    /// Characters to embed, written to the font in ascending order. Duplicates are ignored.
+    pub fn chars(mut self, chars: impl IntoIterator<Item = char>) -> FontBuilder {
+        self.chars = chars.into_iter().collect::<BTreeSet<char>>().into_iter().collect();
+        self
+    }
+
+    /// Resolution the size is given at, in dots per inch. Only used for sizes in points.
+    pub fn dpi(mut self, dpi: u32) -> FontBuilder {
+        self.dpi = dpi;
+        self
+    }
+
+    /// Blank space added to the left of every character, in pixels.
+    pub fn left_spacing(mut self, left_spacing: u8) -> FontBuilder {
+        self.left_spacing = left_spacing;
+        self
+    }
+
+    /// Blank space added to the right of every character, in pixels.
+    pub fn right_spacing(mut self, right_spacing: u8) -> FontBuilder {
+        self.right_spacing = right_spacing;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> FontBuilder {
+        self.compression = compression;
+        self
+    }
+
+    /// Compression level from 0 to 9, only used with [`Compression::Deflate`].
+    pub fn compression_level(mut self, level: i32) -> FontBuilder {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Width of the space in pixels, used instead of the one of the font. Also applies to the space glyph,
+    /// if the characters include it.
+    pub fn space_width(mut self, space_width: u32) -> FontBuilder {
+        self.space_width = Some(space_width);
+        self
+    }
+
+    /// Glyph of U+0000, which MicroUI displays in place of the characters missing from the font. Only used if
+    /// U+0000 is part of the characters, which by default get the first column of the missing glyph of the font.
+    pub fn fallback(mut self, fallback: Fallback) -> FontBuilder {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Width of the [`Fallback::Invisible`] and [`Fallback::Box`] glyphs, in pixels.
+    pub fn fallback_width(mut self, width: u32) -> FontBuilder {
+        self.fallback_width = Some(width);
+        self
+    }
+
+    /// Image of the [`Fallback::Image`] glyph, black on white or on a transparent background.
+    pub fn fallback_image(mut self, path: impl AsRef<Path>) -> FontBuilder {
+        self.fallback_image = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    /// Characters given the same width, centered in it, e.g. the digits of a clock so that it does not jitter.
+    /// The font is monospaced if this includes all of its characters.
+    pub fn monospace(mut self, chars: impl IntoIterator<Item = char>) -> FontBuilder {
+        self.monospace = chars.into_iter().collect();
+        self
+    }
+
+    /// Width of the [monospaced](Self::monospace) characters in pixels, defaults to the width of the widest
+    /// of them.
+    pub fn monospace_width(mut self, width: u32) -> FontBuilder {
+        self.monospace_width = Some(width);
+        self
+    }
+
+    /// OpenType features applied when choosing the glyph of every character, such as `tnum` (tabular
+    /// figures), `onum` (old-style figures), `zero` (slashed zero) or `ss01` (first stylistic set). Features
+    /// are written as in HarfBuzz, e.g. `-liga` to disable one or `salt=2` to pick an alternate.
+    pub fn features(mut self, features: impl IntoIterator<Item = impl Into<String>>) -> FontBuilder {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Number of threads rendering the characters, defaults to the number of available CPUs.
+    pub fn threads(mut self, threads: usize) -> FontBuilder {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Called after every character with the number of characters written so far and the total.
+    pub fn on_progress(mut self, callback: impl Fn((i32, i32)) + Send + Sync + 'static) -> FontBuilder {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Build the font and write it to the given file, replacing it if it exists.
+    pub fn build_to_file(&self, path: impl AsRef<Path>) -> Result<EjfResult, Error> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => get_font_name(&path)?
+        };
+
+        let file = File::create(&path).map_err(|e| Error::io(&path, e))?;
+        self.write(file, &name, &path, |progress| self.report_progress(progress))
+    }
+
+    /// Build the font and write it to the given writer.
+    pub fn build_to_writer<W: Write + Seek>(&self, writer: W) -> Result<EjfResult, Error> {
+        let name = match (&self.name, &self.input) {
+            (Some(name), _) => name.clone(),
+            (None, FontSource::File(path)) => get_font_name(path)?,
+            (None, FontSource::Memory(_)) => return Err(Error::NameError { path: self.input.to_string() })
+        };
+
+        self.write(writer, &name, &name, |progress| self.report_progress(progress))
+    }
+
+    /// Open the font at the given size. FreeType faces cannot be shared between threads, so every rendering
+    /// thread opens its own from the same data.
+    fn open_face<'a>(&self, data: &'a [u8], size: FaceSize) -> Result<Face<&'a [u8]>, Error> {
+        let library = Library::init()?;
+        let face = library.new_memory_face2(data, 0)
+            .map_err(|source| Error::FontLoadError { path: self.input.to_string(), source })?;
+
+        face.set_char_size(size.char_width, 0, size.dpi, 0)
+            .map_err(|source| Error::FontSizeError { size: self.size, source })?;
+        Ok(face)
+    }
+
+    /// Determine the size to open the font at from the configured size and unit. Cap and line heights are
+    /// searched for, as the largest size whose cap or line height is not higher than the wanted one.
+    fn face_size<'a>(&self, data: &'a [u8]) -> Result<FaceSize, Error> {
+        let char_width = self.size as isize * 64;
+        let measure: fn(&Face<&'a [u8]>) -> Result<u32, Error> = match self.size_unit {
+            SizeUnit::Pt => return Ok(FaceSize { char_width, dpi: self.dpi }),
+            SizeUnit::Px => return Ok(FaceSize { char_width, dpi: PIXEL_DPI }),
+            SizeUnit::CapHeightPx => cap_height,
+            SizeUnit::LineHeightPx => |face| Ok(determine_metrics_from_font(face)?.height)
+        };
+
+        let face = self.open_face(data, FaceSize { char_width, dpi: PIXEL_DPI })?;
+        let measure_at = |char_width: isize| {
+            face.set_char_size(char_width, 0, PIXEL_DPI, 0)
+                .map_err(|source| Error::FontSizeError { size: self.size, source })?;
+            measure(&face)
+        };
+
+        // Binary search between 1/64px and a size much larger than any cap or line height.
+        let (mut low, mut high) = (1, max(1, char_width * 8));
+        while low < high {
+            let middle = (low + high + 1) / 2;
+            if measure_at(middle)? <= self.size {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+
+        // The heights are rounded, so they may skip the wanted one from a size to the next.
+        match self.size > 0 && measure_at(low)? == self.size {
+            true => Ok(FaceSize { char_width: low, dpi: PIXEL_DPI }),
+            false => Err(Error::SizeSearchError { size: self.size, unit: self.size_unit })
+        }
+    }
+
+    fn fallback_glyph(&self) -> Result<Option<FallbackGlyph>, Error> {
+        Ok(match self.fallback {
+            None => None,
+            Some(Fallback::Invisible) => Some(FallbackGlyph::Invisible { width: self.fallback_width.unwrap_or(1) }),
+            Some(Fallback::Box) => Some(FallbackGlyph::Box { width: self.fallback_width }),
+            Some(Fallback::Replacement) => Some(FallbackGlyph::Replacement),
+            Some(Fallback::Image) => {
+                let path = self.fallback_image.as_ref().ok_or_else(|| Error::MissingOptionError {
+                    option: "fallback_image".to_string(),
+                    required_by: "the image fallback".to_string()
+                })?;
+                let data = fs::read(path).map_err(|e| Error::io(path, e))?;
+                let image = image::load_from_memory(&data).map_err(|source| Error::ImageError { ch: '\0', source })?.to_luma_alpha8();
+                // Transparent pixels are drawn over the white background of the glyph.
+                let image = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                    let LumaA([luma, alpha]) = *image.get_pixel(x, y);
+                    let (luma, alpha, max) = (luma as u32, alpha as u32, u8::MAX as u32);
+                    Luma([((luma * alpha + max * (max - alpha)) / max) as u8])
+                });
+                Some(FallbackGlyph::Image(image))
+            }
+        })
+    }
+
+    fn parse_features(&self) -> Result<Vec<Feature>, Error> {
+        self.features.iter()
+            .map(|feature| feature.parse::<Feature>().map_err(|_| Error::FeatureError { feature: feature.clone() }))
+            .collect()
+    }
+
+    /// Open the font to read its OpenType tables, which FreeType does not give access to.
+    fn open_opentype_face<'a>(&self, data: &'a [u8]) -> Result<rustybuzz::Face<'a>, Error> {
+        rustybuzz::Face::from_slice(data, 0)
+            .ok_or_else(|| Error::OpenTypeError { path: self.input.to_string() })
+    }
+
+    /// Shape every character on its own with the OpenType features, to find the glyphs that replace the default
+    /// ones. Characters shaped into several glyphs (or none) keep their default glyph.
+    fn substitute_glyphs(&self, data: &[u8]) -> Result<HashMap<char, u32>, Error> {
+        let mut glyphs = HashMap::new();
+        if self.features.is_empty() {
+            return Ok(glyphs);
+        }
+
+        let features = self.parse_features()?;
+        let face = self.open_opentype_face(data)?;
+
+        for ch in &self.chars {
+            let mut buffer = UnicodeBuffer::new();
+            buffer.add(*ch, 0);
+            let shaped = rustybuzz::shape(&face, &features, buffer);
+            let default_glyph = face.glyph_index(*ch).map(|glyph_id| glyph_id.0 as u32);
+            if let [glyph] = shaped.glyph_infos() {
+                if glyph.glyph_id != 0 && Some(glyph.glyph_id) != default_glyph {
+                    glyphs.insert(*ch, glyph.glyph_id);
+                }
+            }
+        }
+        Ok(glyphs)
+    }
+
+    /// Width of the monospaced characters, `None` if no character of the font is monospaced.
+    fn measure_monospace_width<B>(&self, face: &Face<B>, render_config: &RenderConfig) -> Result<Option<u32>, Error> {
+        let mut chars = self.chars.iter().filter(|ch| self.monospace.contains(ch)).peekable();
+        if chars.peek().is_none() {
+            return Ok(None);
+        }
+        if let Some(width) = self.monospace_width {
+            return Ok(Some(width));
+        }
+
+        let mut max_width = 0;
+        for ch in chars {
+            max_width = max(max_width, renderer::get_char_width(face, *ch, render_config)?);
+        }
+        Ok(Some(max_width))
+    }
+
+    /// The kerning of every pair of characters in pixels, from the GPOS or `kern` table of the font and taking the
+    /// [features](Self::features) into account. Pairs without kerning are left out.
+    pub fn kerning_pairs(&self) -> Result<Vec<KerningPair>, Error> {
+        let data = self.input.read()?;
+        let face = self.open_face(&data, self.face_size(&data)?)?;
+        let x_scale = face.size_metrics().ok_or(Error::MetricsError)?.x_scale as f32 / 65536.0 / 64.0;
+        Ok(kerning::extract_pairs(&self.open_opentype_face(&data)?, &self.parse_features()?, &self.chars, x_scale))
+    }
+
+    /// Write the [kerning pairs](Self::kerning_pairs) to the given file, in the format given by its extension.
+    /// Returns the number of pairs.
+    pub fn build_kerning_to_file(&self, path: impl AsRef<Path>) -> Result<usize, Error> {
+        let path = path.as_ref();
+        let path_string = path.to_string_lossy().to_string();
+        let format = KerningFormat::from_path(path)
+            .ok_or_else(|| Error::KerningFormatError { path: path_string.clone() })?;
+
+        // Java classes are named after their file.
+        let name = match (format, &self.name) {
+            (KerningFormat::Java, _) => kerning::java_class_name(path, &get_font_name(&path_string)?),
+            (_, Some(name)) => name.clone(),
+            (_, None) => get_font_name(&path_string)?
+        };
+
+        let pairs = self.kerning_pairs()?;
+        let mut data = Vec::new();
+        kerning::write_kerning(&pairs, format, &name, &mut data).map_err(|e| Error::io(&path_string, e))?;
+        fs::write(path, data).map_err(|e| Error::io(&path_string, e))?;
+        Ok(pairs.len())
+    }
+
+    fn report_progress(&self, progress: (i32, i32)) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+
+    /// Write the archive, `path` being only used to describe the destination in errors.
+    ///
+    /// The characters are rendered in parallel, but the archive only depends on the settings and on the input
+    /// font: every entry has a fixed timestamp and permissions, and the entries are always written in the same
+    /// order (characters, then the header).
+    pub(super) fn write<W, F>(&self, mut writer: W, name: &str, path: &str, progress_callback: F) -> Result<EjfResult, Error>
+        where W: Write + Seek, F: Fn((i32, i32))
+    {
+        // The ZIP library only reports an invalid level when writing the first entry, as a generic error.
+        if let Some(level) = self.compression_level.filter(|level| !(0..=9).contains(level)) {
+            return Err(Error::CompressionLevelError { level });
+        }
+
+        let zip_error = |source| Error::ZipWriterError { path: path.to_string(), source };
+        // The writer does not necessarily start at the beginning of the stream.
+        let archive_start = writer.stream_position().map_err(|e| zip_error(e.into()))?;
+        let mut zip = ZipWriter::new(writer);
+
+        // Try to open the font.
+        let data = self.input.read()?;
+        let face_size = self.face_size(&data)?;
+        let face = self.open_face(&data, face_size)?;
+
+        // Determine max height.
+        let metrics = determine_metrics_from_font(&face)?;
+        let image_height = metrics.height;
+
+        // Render the characters.
+        let zip_options = FileOptions::default()
+            .last_modified_time(DateTime::default())
+            .unix_permissions(ENTRY_PERMISSIONS)
+            .compression_method(self.compression.into())
+            .compression_level(match self.compression {
+                Compression::Stored => None,
+                Compression::Deflate => self.compression_level
+            });
+        let render_config = RenderConfig {
+            left_spacing: self.left_spacing,
+            right_spacing: self.right_spacing,
+            max_ascent: metrics.ascent,
+            total_height: image_height,
+            space_width: self.space_width,
+            fallback: self.fallback_glyph()?,
+            glyphs: self.substitute_glyphs(&data)?
+        };
+        let monospace_width = self.measure_monospace_width(&face, &render_config)?;
+
+        let num_threads = self.threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()))
+            .clamp(1, max(1, self.chars.len()));
+        let next_char = AtomicUsize::new(0);
+        let cancelled = AtomicBool::new(false);
+
+        let (missing_glyphs, clipped_glyphs) = thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..num_threads {
+                let sender = sender.clone();
+                let (data, render_config, next_char, cancelled) = (data.as_slice(), &render_config, &next_char, &cancelled);
+                let width_of = move |ch: &char| monospace_width.filter(|_| self.monospace.contains(ch));
+
+                scope.spawn(move || {
+                    let face = match self.open_face(data, face_size) {
+                        Ok(face) => face,
+                        Err(error) => {
+                            // Reported in place of the next character, which is then rendered by no other thread.
+                            let _ = sender.send((next_char.fetch_add(1, Ordering::Relaxed), Err(error)));
+                            return;
+                        }
+                    };
+
+                    while !cancelled.load(Ordering::Relaxed) {
+                        let index = next_char.fetch_add(1, Ordering::Relaxed);
+                        let Some(ch) = self.chars.get(index) else { break };
+                        // A panic is reported as the error of the character, instead of leaving it unrendered.
+                        let rendered = panic::catch_unwind(AssertUnwindSafe(|| render_char(&face, *ch, render_config, width_of(ch))))
+                            .unwrap_or_else(|payload| Err(Error::panic(payload)));
+                        if sender.send((index, rendered)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Characters arrive in the order they are rendered, but are written in the order of the list.
+            let mut write_chars = || -> Result<(Vec<char>, Vec<char>), Error> {
+                let mut pending = HashMap::new();
+                let mut missing_glyphs = Vec::new();
+                let mut clipped_glyphs = Vec::new();
+                let mut num_processed = 0;
+                while num_processed < self.chars.len() {
+                    let rendered = match pending.remove(&num_processed) {
+                        Some(rendered) => rendered,
+                        None => {
+                            let (index, rendered) = receiver.recv()
+                                .map_err(|_| Error::PanicError { message: "A rendering thread stopped unexpectedly".to_string() })?;
+                            pending.insert(index, rendered);
+                            continue;
+                        }
+                    };
+
+                    let ch = self.chars[num_processed];
+                    let rendered = rendered?;
+                    if rendered.missing {
+                        missing_glyphs.push(ch);
+                    }
+                    if rendered.clipped {
+                        clipped_glyphs.push(ch);
+                    }
+
+                    // Write the character to the zip file
+                    let char_code = format!("0x{:x}", ch as u32);
+                    zip.start_file(&char_code, zip_options).map_err(zip_error)?;
+                    zip.write_all(&rendered.image_data).map_err(|e| zip_error(e.into()))?;
+
+                    // Also write the "design" character to the zip file.
+                    zip.start_file(format!("design_{}", &char_code), zip_options).map_err(zip_error)?;
+                    zip.write_all(&rendered.image_data).map_err(|e| zip_error(e.into()))?;
+
+                    num_processed += 1;
+                    progress_callback((num_processed as i32, self.chars.len() as i32));
+                }
+                Ok((missing_glyphs, clipped_glyphs))
+            };
+
+            let result = write_chars();
+            cancelled.store(true, Ordering::Relaxed);
+            result
+        })?;
+
+        // Write the header
+        // The space of a monospaced font is as wide as the other characters, unless its width is given.
+        let width = monospace_width.filter(|_| self.chars.iter().all(|ch| self.monospace.contains(ch)));
+        let space_width = match (width, self.space_width) {
+            (Some(width), None) => width,
+            _ => renderer::get_char_width(&face, ' ', &render_config)?
+        };
+        let glyph_count = self.chars.len();
+        let baseline = metrics.ascent as u32;
+        let header = header::write_header(HeaderInfo {
+            chars: self.chars.clone(),
+            height: image_height,
+            baseline: header::HEADER_BASELINE,
+            name: name.to_string(),
+            space_width,
+            width
+        })?;
+        zip.start_file("Header", zip_options).map_err(zip_error)?;
+        zip.write_all(&header).map_err(|e| zip_error(e.into()))?;
+        let archive_size = zip.finish().map_err(zip_error)?
+            .stream_position().map_err(|e| zip_error(e.into()))? - archive_start;
+
+        Ok(EjfResult {
+            height: image_height,
+            name: name.to_string(),
+            space_width,
+            baseline,
+            glyph_count,
+            missing_glyphs,
+            clipped_glyphs,
+            archive_size
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::ZipArchive;
+    use super::*;
+
+    const FONT: &[u8] = include_bytes!("../../samples/FiraSans/FiraSans-Regular.ttf");
+
+    fn builder(threads: usize) -> FontBuilder {
+        FontBuilder::new(FONT)
+            .name("FiraSans")
+            .size(16)
+            .chars((' '..='~').chain('À'..='ÿ'))
+            .threads(threads)
+    }
+
+    fn build(threads: usize) -> Vec<u8> {
+        let mut archive = Cursor::new(Vec::new());
+        builder(threads).build_to_writer(&mut archive).unwrap();
+        archive.into_inner()
+    }
+
+    #[test]
+    fn archive_does_not_depend_on_the_number_of_threads() {
+        assert!(build(1) == build(8));
+    }
+
+    #[test]
+    fn entries_have_a_fixed_timestamp_and_permissions() {
+        let mut archive = ZipArchive::new(Cursor::new(build(2))).unwrap();
+        let fixed = DateTime::default();
+        for index in 0..archive.len() {
+            let entry = archive.by_index(index).unwrap();
+            let modified = entry.last_modified();
+            assert_eq!((modified.datepart(), modified.timepart()), (fixed.datepart(), fixed.timepart()), "{}", entry.name());
+            assert_eq!(entry.unix_mode().map(|mode| mode & 0o777), Some(ENTRY_PERMISSIONS), "{}", entry.name());
+        }
+    }
+
+    #[test]
+    fn archive_size_excludes_the_data_before_it() {
+        let mut data = Cursor::new(vec![0; 100]);
+        data.set_position(100);
+        let result = builder(2).build_to_writer(&mut data).unwrap();
+        assert_eq!(result.archive_size, data.into_inner().len() as u64 - 100);
+    }
+}
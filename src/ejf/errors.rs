@@ -1,63 +1,176 @@
 use quick_xml::Error as XmlError;
 use zip::result::ZipError;
-use std::io::Error as IoError;
+use std::{any::Any, fmt, io::Error as IoError};
 use image::ImageError;
 use freetype::Error as FreeTypeError;
-use super::char_range::ParseError as RangeParseError;
+use crate::char_range::ParseError as RangeParseError;
+use super::SizeUnit;
 
+#[allow(clippy::enum_variant_names)]
+#[non_exhaustive]
+#[derive(Debug)]
 pub enum Error {
 
     /// Error when writing the XML Header of the .ejf file.
     XmlWriterError(XmlError),
 
+    /// Error when reading the XML Header of an existing .ejf file.
+    XmlReaderError(XmlError),
+
     /// Error when writing the zip container of the .ejf data.
-    ZipWriterError(ZipError),
+    ZipWriterError { path: String, source: ZipError },
+
+    /// Error when reading the zip container of an existing .ejf file.
+    ZipReaderError(ZipError),
 
-    /// Input/output error when writing the zip/ejf file.
-    IoError(IoError),
+    /// Input/output error when reading or writing a file.
+    IoError { path: String, source: IoError },
 
     /// Unable to determine the name of the resulting font (.ejf) based on the path.
-    NameError,
+    NameError { path: String },
 
-    /// Error while generating character images.
-    ImageError(ImageError),
+    /// Error while generating the image of a character.
+    ImageError { ch: char, source: ImageError },
 
-    /// Error while initializing or using the FreeType engine.
+    /// Error while initializing the FreeType engine.
     FreeTypeError(FreeTypeError),
 
+    /// Unable to open the input font.
+    FontLoadError { path: String, source: FreeTypeError },
+
+    /// Unable to set the size of the input font.
+    FontSizeError { size: u32, source: FreeTypeError },
+
+    /// No size of the input font has the given cap height or line height.
+    SizeSearchError { size: u32, unit: SizeUnit },
+
+    /// Unable to load or render a character from the input font.
+    GlyphLoadError { ch: char, source: FreeTypeError },
+
     /// Error when parsing the list of characters to be imported.
     RangeParseError(RangeParseError),
 
-    /// Unable to determine the character metrics.
-    MetricsError
+    /// Invalid glob pattern in `chars_from`.
+    PatternError { pattern: String, source: glob::PatternError },
+
+    /// A glob pattern in `chars_from` does not match any file.
+    NoMatchError { pattern: String },
+
+    /// Unable to extract the characters from a file listed in `chars_from`.
+    ExtractError { path: String, source: Box<dyn std::error::Error + Send + Sync> },
+
+    /// Unable to read a manifest, or a file it includes.
+    ConfigReadError { path: String, source: IoError },
+
+    /// A manifest, or a file it includes, is not valid TOML or has invalid options.
+    ConfigParseError { path: String, source: toml::de::Error },
+
+    /// Invalid line in a `.fonts.list` file.
+    FontsListError { line: usize, message: String },
+
+    /// An option is required by another one, e.g. `fallback_image` for `fallback = "image"`.
+    MissingOptionError { option: String, required_by: String },
+
+    /// Invalid OpenType feature in `features`, e.g. a tag longer than 4 characters.
+    FeatureError { feature: String },
+
+    /// The compression level is not between 0 and 9.
+    CompressionLevelError { level: i32 },
+
+    /// The input font has no OpenType tables the features or the kerning could be read from.
+    OpenTypeError { path: String },
+
+    /// The format of a kerning file cannot be determined from its extension.
+    KerningFormatError { path: String },
+
+    /// Unable to determine the metrics of the font.
+    MetricsError,
+
+    /// The header of an existing .ejf file is missing required information.
+    InvalidHeader,
+
+    /// The generation of the font was interrupted by an unexpected failure.
+    PanicError { message: String }
 
 }
 
-impl From<XmlError> for Error {
-    #[inline]
-    fn from(error: XmlError) -> Self {
-        Error::XmlWriterError(error)
+impl Error {
+    pub fn io(path: &str, source: IoError) -> Self {
+        Error::IoError { path: path.to_string(), source }
+    }
+
+    /// The error of a thread that panicked, from the payload of the panic.
+    pub fn panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Unknown error".to_string()
+            }
+        };
+        Error::PanicError { message }
     }
 }
 
-impl From<ZipError> for Error {
-    #[inline]
-    fn from(error: ZipError) -> Self {
-        Error::ZipWriterError(error)
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::XmlWriterError(_) => write!(f, "Unable to write the header XML file"),
+            Error::XmlReaderError(_) => write!(f, "Unable to read the header XML file"),
+            Error::ZipWriterError { path, .. } => write!(f, "Unable to write the .ejf file at '{}'", path),
+            Error::ZipReaderError(_) => write!(f, "Unable to read the .ejf file"),
+            Error::IoError { path, .. } => write!(f, "Unable to read or write the file at '{}'", path),
+            Error::NameError { path } => write!(f, "Unable to determine the name of the resulting font (.ejf) based on the path '{}'", path),
+            Error::ImageError { ch, .. } => write!(f, "Unable to generate the image of the character 0x{:x}", *ch as u32),
+            Error::FreeTypeError(_) => write!(f, "Unable to initialize FreeType"),
+            Error::FontLoadError { path, .. } => write!(f, "Unable to open the font at '{}'", path),
+            Error::FontSizeError { size, .. } => write!(f, "Unable to set the size of the font to {}", size),
+            Error::SizeSearchError { size, unit } => write!(f, "No size of the font has a {} of {}px", unit, size),
+            Error::GlyphLoadError { ch, .. } => write!(f, "Unable to load the character 0x{:x} from the font", *ch as u32),
+            Error::RangeParseError(error) => write!(f, "Unable to parse the given character range at '{}'", error.input),
+            Error::PatternError { pattern, .. } => write!(f, "Invalid file pattern '{}'", pattern),
+            Error::NoMatchError { pattern } => write!(f, "No file matches the pattern '{}'", pattern),
+            Error::ExtractError { path, .. } => write!(f, "Unable to extract the characters from '{}'", path),
+            Error::ConfigReadError { path, .. } => write!(f, "Unable to open the configuration file at '{}'", path),
+            Error::ConfigParseError { path, .. } => write!(f, "Unable to parse the configuration file at '{}'", path),
+            Error::FontsListError { line, message } => write!(f, "Invalid entry at line {} of the .fonts.list: {}", line, message),
+            Error::MissingOptionError { option, required_by } => write!(f, "The option '{}' is required by {}", option, required_by),
+            Error::FeatureError { feature } => write!(f, "Invalid OpenType feature '{}'", feature),
+            Error::CompressionLevelError { level } => write!(f, "Invalid compression level {}, expected a level from 0 to 9", level),
+            Error::OpenTypeError { path } => write!(f, "Unable to read the OpenType tables of the font at '{}'", path),
+            Error::KerningFormatError { path } => write!(f, "Unknown format of the kerning file '{}', expected a .json, .bin, .c, .h or .java file", path),
+            Error::MetricsError => write!(f, "Unable to determine the metrics of the font"),
+            Error::InvalidHeader => write!(f, "The header of the .ejf file is missing or incomplete"),
+            Error::PanicError { message } => write!(f, "Unexpected failure while generating the font: {}", message)
+        }
     }
 }
 
-impl From<IoError> for Error {
-    #[inline]
-    fn from(error: IoError) -> Self {
-        Error::IoError(error)
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::XmlWriterError(source) | Error::XmlReaderError(source) => Some(source),
+            Error::ZipWriterError { source, .. } | Error::ZipReaderError(source) => Some(source),
+            Error::IoError { source, .. } | Error::ConfigReadError { source, .. } => Some(source),
+            Error::ConfigParseError { source, .. } => Some(source),
+            Error::ImageError { source, .. } => Some(source),
+            Error::FreeTypeError(source)
+                | Error::FontLoadError { source, .. }
+                | Error::FontSizeError { source, .. }
+                | Error::GlyphLoadError { source, .. } => Some(source),
+            Error::RangeParseError(source) => Some(source),
+            Error::PatternError { source, .. } => Some(source),
+            Error::ExtractError { source, .. } => Some(source.as_ref()),
+            Error::NameError { .. } | Error::SizeSearchError { .. } | Error::NoMatchError { .. } | Error::FontsListError { .. } | Error::MissingOptionError { .. }
+                | Error::FeatureError { .. } | Error::CompressionLevelError { .. } | Error::OpenTypeError { .. } | Error::KerningFormatError { .. } | Error::MetricsError | Error::InvalidHeader | Error::PanicError { .. } => None
+        }
     }
 }
 
-impl From<ImageError> for Error {
+impl From<XmlError> for Error {
     #[inline]
-    fn from(error: ImageError) -> Self {
-        Error::ImageError(error)
+    fn from(error: XmlError) -> Self {
+        Error::XmlWriterError(error)
     }
 }
 
@@ -73,4 +186,4 @@ impl From<RangeParseError> for Error {
     fn from(error: RangeParseError) -> Self {
         Error::RangeParseError(error)
     }
-}
\ No newline at end of file
+}
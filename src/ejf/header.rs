@@ -1,10 +1,20 @@
-use quick_xml::{Writer, Error};
+use quick_xml::{Reader, Writer, Error, events::{Event, BytesStart}};
 
+use crate::char_range::parse_single_charcode;
+
+/// Baseline written in the header of every font, the same as the original generator so that the fonts it
+/// generated are unchanged. The actual baseline of a font is only reported.
+pub const HEADER_BASELINE: u32 = 13;
+
+#[derive(Debug, PartialEq)]
 pub struct HeaderInfo {
     pub chars: Vec<char>,
     pub height: u32,
+    pub baseline: u32,
     pub name: String,
-    pub space_width: u32
+    pub space_width: u32,
+    /// Width of every character, if the font is monospaced.
+    pub width: Option<u32>
 }
 
 fn write_informations(writer: &mut Writer<Vec<u8>>) -> Result<(), Error> {
@@ -19,16 +29,17 @@ fn write_informations(writer: &mut Writer<Vec<u8>>) -> Result<(), Error> {
 }
 
 fn write_font_properties(writer: &mut Writer<Vec<u8>>, data: &HeaderInfo) -> Result<(), Error> {
+    let width = data.width.map_or("-1".to_string(), |width| width.to_string());
     writer
         .create_element("FontProperties")
         .with_attributes(vec![
-            ("Baseline", "13"),
+            ("Baseline", data.baseline.to_string().as_str()),
             ("Filter", ""),
             ("Height", data.height.to_string().as_str()),
             ("Name", data.name.as_str()),
             ("Space", data.space_width.to_string().as_str()),
             ("Style", "p"),
-            ("Width", "-1")
+            ("Width", width.as_str())
         ])
         .write_inner_content(|writer| {
             writer.create_element("Identifier")
@@ -75,4 +86,88 @@ pub fn write_header(data: HeaderInfo) -> Result<Vec<u8>, Error> {
             Ok(())
         })?;
     Ok(writer.inner().to_vec())
-}
\ No newline at end of file
+}
+
+fn read_attribute(reader: &Reader<&[u8]>, element: &BytesStart, name: &[u8]) -> Result<Option<String>, Error> {
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        if attribute.key == name {
+            return Ok(Some(attribute.unescape_and_decode_value(reader)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Read back the information written by `write_header`, `None` if the header is incomplete.
+pub fn read_header(data: &[u8]) -> Result<Option<HeaderInfo>, Error> {
+    let mut reader = Reader::from_reader(data);
+    let mut buffer = Vec::new();
+    let mut name = None;
+    let mut height = None;
+    let mut baseline = None;
+    let mut space_width = None;
+    let mut width = None;
+    let mut chars = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buffer)? {
+            Event::Start(element) | Event::Empty(element) => match element.name() {
+                b"FontProperties" => {
+                    name = read_attribute(&reader, &element, b"Name")?;
+                    height = read_attribute(&reader, &element, b"Height")?.and_then(|value| value.parse().ok());
+                    baseline = read_attribute(&reader, &element, b"Baseline")?.and_then(|value| value.parse().ok());
+                    space_width = read_attribute(&reader, &element, b"Space")?.and_then(|value| value.parse().ok());
+                    // -1 if the font is not monospaced.
+                    width = read_attribute(&reader, &element, b"Width")?.and_then(|value| value.parse().ok());
+                },
+                b"Character" => {
+                    let ch = read_attribute(&reader, &element, b"Index")?
+                        .and_then(|index| parse_single_charcode(&index).ok())
+                        .and_then(char::from_u32);
+                    if let Some(ch) = ch {
+                        chars.push(ch);
+                    }
+                },
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+
+    Ok(match (name, height, baseline, space_width) {
+        (Some(name), Some(height), Some(baseline), Some(space_width)) => Some(HeaderInfo { chars, height, baseline, name, space_width, width }),
+        _ => None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: Option<u32>) -> HeaderInfo {
+        HeaderInfo {
+            chars: vec!['\0', ' ', 'A', 'é', '😀'],
+            height: 19,
+            baseline: HEADER_BASELINE,
+            name: "Roboto <16> & \"Co\"".to_string(),
+            space_width: 4,
+            width
+        }
+    }
+
+    #[test]
+    fn header_round_trip() {
+        for width in [None, Some(9)] {
+            let data = write_header(header(width)).unwrap();
+            assert_eq!(read_header(&data).unwrap(), Some(header(width)));
+        }
+    }
+
+    #[test]
+    fn incomplete_header() {
+        let data = br#"<FontGenerator><FontProperties Name="Roboto" Height="19"/></FontGenerator>"#;
+        assert_eq!(read_header(data).unwrap(), None);
+    }
+}
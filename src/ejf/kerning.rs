@@ -0,0 +1,273 @@
+use std::{collections::HashMap, io::{self, Write}, path::Path};
+use rustybuzz::{ttf_parser::{gpos::PositioningSubtable, kern, GlyphId}, Face, Feature, UnicodeBuffer};
+use serde::Serialize;
+
+/// Magic number at the start of the binary kerning files, followed by the version of the format.
+const BINARY_MAGIC: &[u8; 4] = b"EJFK";
+const BINARY_VERSION: u8 = 1;
+
+/// The adjustment of the space between two characters, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KerningPair {
+    pub left: char,
+    pub right: char,
+    /// Added to the distance between the characters, negative to bring them closer.
+    pub adjustment: i32
+}
+
+/// Format of a kerning file, determined by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KerningFormat {
+    /// `.json`, an object with the name of the font and its pairs.
+    Json,
+    /// `.bin`, `EJFK` and a version byte, the number of pairs as an `u32`, then every pair as the code points
+    /// of its characters (`u32`) and its adjustment (`i16`), all little-endian.
+    Binary,
+    /// `.c` or `.h`, an array of structures ended by a zero entry.
+    C,
+    /// `.java`, a class named after the file, with the pairs as triples of an `int` array.
+    Java
+}
+
+impl KerningFormat {
+    pub fn from_path(path: &Path) -> Option<KerningFormat> {
+        match path.extension()?.to_str()? {
+            "json" => Some(KerningFormat::Json),
+            "bin" => Some(KerningFormat::Binary),
+            "c" | "h" => Some(KerningFormat::C),
+            "java" => Some(KerningFormat::Java),
+            _ => None
+        }
+    }
+}
+
+/// Horizontal advance of the glyphs of the text, in font units.
+fn shape_advances(face: &Face, features: &[Feature], text: &[char]) -> Vec<i32> {
+    let mut buffer = UnicodeBuffer::new();
+    for (cluster, ch) in text.iter().enumerate() {
+        buffer.add(*ch, cluster as u32);
+    }
+    rustybuzz::shape(face, features, buffer).glyph_positions().iter()
+        .map(|position| position.x_advance)
+        .collect()
+}
+
+/// Whether the glyph can be the first of a pair positioned by the GPOS or the `kern` table.
+fn has_pair_adjustment(face: &Face, glyph_id: GlyphId) -> bool {
+    let in_gpos = face.tables().gpos.is_some_and(|gpos| gpos.lookups.into_iter()
+        .any(|lookup| lookup.subtables.into_iter::<PositioningSubtable>()
+            .any(|subtable| matches!(subtable, PositioningSubtable::Pair(pairs) if pairs.coverage().contains(glyph_id)))));
+
+    let in_kern = face.tables().kern.is_some_and(|kern| kern.subtables.into_iter()
+        .filter(|subtable| subtable.horizontal)
+        .any(|subtable| match subtable.format {
+            // The pairs are sorted by their left glyph, then by their right one.
+            kern::Format::Format0(subtable) => subtable.pairs.binary_search_by(|pair| pair.left().cmp(&glyph_id)).is_some(),
+            // The glyphs of the other formats cannot be listed, so any of them may be kerned.
+            _ => true
+        }));
+
+    in_gpos || in_kern
+}
+
+/// Find the kerning of every pair of characters by shaping them, so that it includes both the GPOS and the `kern`
+/// table along with the features. `x_scale` converts font units to pixels.
+pub(super) fn extract_pairs(face: &Face, features: &[Feature], chars: &[char], x_scale: f32) -> Vec<KerningPair> {
+    if face.tables().gpos.is_none() && face.tables().kern.is_none() {
+        return Vec::new();
+    }
+
+    // The NULL character is only a fallback, and is never displayed next to another one.
+    let chars: Vec<char> = chars.iter().copied().filter(|ch| *ch != '\0').collect();
+    let advances: HashMap<char, i32> = chars.iter()
+        .filter_map(|ch| match shape_advances(face, features, &[*ch])[..] {
+            [advance] => Some((*ch, advance)),
+            _ => None
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for left in chars.iter().filter(|ch| face.glyph_index(**ch).is_some_and(|id| id != GlyphId(0) && has_pair_adjustment(face, id))) {
+        let Some(advance) = advances.get(left) else {
+            continue;
+        };
+
+        for right in &chars {
+            // Pairs shaped into a single glyph, such as ligatures, cannot be kerned.
+            let [pair_advance, _] = shape_advances(face, features, &[*left, *right])[..] else {
+                continue;
+            };
+
+            let adjustment = ((pair_advance - advance) as f32 * x_scale).round() as i32;
+            if adjustment != 0 {
+                pairs.push(KerningPair { left: *left, right: *right, adjustment });
+            }
+        }
+    }
+    pairs
+}
+
+/// A valid C or Java identifier made of the letters and digits of the name.
+fn identifier(name: &str) -> String {
+    let identifier: String = name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect();
+    match identifier.starts_with(|ch: char| ch.is_ascii_digit()) || identifier.is_empty() {
+        true => format!("_{}", identifier),
+        false => identifier
+    }
+}
+
+/// The qualified name of a Java class written to the given path, which is part of the package of the directories
+/// following the `java` source directory (e.g. `src/main/java/com/mycompany/Roboto16Kerning.java`).
+pub(super) fn java_class_name(path: &Path, class: &str) -> String {
+    let mut components: Vec<String> = path.parent().into_iter()
+        .flat_map(Path::components)
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let package = match components.iter().rposition(|component| component == "java") {
+        Some(index) => components.split_off(index + 1),
+        None => Vec::new()
+    };
+    package.into_iter().chain([class.to_string()]).collect::<Vec<String>>().join(".")
+}
+
+#[derive(Serialize)]
+struct JsonKerning<'a> {
+    name: &'a str,
+    pairs: Vec<JsonPair>
+}
+
+#[derive(Serialize)]
+struct JsonPair {
+    left: u32,
+    right: u32,
+    adjustment: i32
+}
+
+/// Write the kerning of the font in the given format. `name` is the name of the font, or the qualified name of
+/// the class for Java (e.g. `com.mycompany.Roboto16Kerning`).
+pub fn write_kerning<W: Write>(pairs: &[KerningPair], format: KerningFormat, name: &str, mut writer: W) -> io::Result<()> {
+    match format {
+        KerningFormat::Json => {
+            let kerning = JsonKerning {
+                name,
+                pairs: pairs.iter()
+                    .map(|pair| JsonPair { left: pair.left as u32, right: pair.right as u32, adjustment: pair.adjustment })
+                    .collect()
+            };
+            serde_json::to_writer_pretty(&mut writer, &kerning)?;
+            writeln!(writer)
+        },
+        KerningFormat::Binary => {
+            writer.write_all(BINARY_MAGIC)?;
+            writer.write_all(&[BINARY_VERSION])?;
+            writer.write_all(&(pairs.len() as u32).to_le_bytes())?;
+            for pair in pairs {
+                writer.write_all(&(pair.left as u32).to_le_bytes())?;
+                writer.write_all(&(pair.right as u32).to_le_bytes())?;
+                writer.write_all(&(pair.adjustment as i16).to_le_bytes())?;
+            }
+            Ok(())
+        },
+        KerningFormat::C => {
+            writeln!(writer, "// Kerning of the font {}, in pixels, generated by ejf-utils.", name)?;
+            writeln!(writer, "#include <stdint.h>")?;
+            writeln!(writer)?;
+            writeln!(writer, "static const struct {{ uint32_t left; uint32_t right; int16_t adjustment; }} {}_kerning[] = {{", identifier(name))?;
+            for pair in pairs {
+                writeln!(writer, "    {{ 0x{:x}, 0x{:x}, {} }},", pair.left as u32, pair.right as u32, pair.adjustment)?;
+            }
+            writeln!(writer, "    {{ 0, 0, 0 }}")?;
+            writeln!(writer, "}};")
+        },
+        KerningFormat::Java => {
+            let (package, name) = name.rsplit_once('.').map_or((None, name), |(package, name)| (Some(package), name));
+            writeln!(writer, "// Kerning of the font, in pixels, generated by ejf-utils.")?;
+            if let Some(package) = package {
+                writeln!(writer, "package {};", package)?;
+                writeln!(writer)?;
+            }
+            writeln!(writer, "public final class {} {{", identifier(name))?;
+            writeln!(writer)?;
+            writeln!(writer, "    /** The left character, the right character and the adjustment of every pair. */")?;
+            writeln!(writer, "    public static final int[] PAIRS = {{")?;
+            for pair in pairs {
+                writeln!(writer, "        0x{:x}, 0x{:x}, {},", pair.left as u32, pair.right as u32, pair.adjustment)?;
+            }
+            writeln!(writer, "    }};")?;
+            writeln!(writer)?;
+            writeln!(writer, "    private {}() {{", identifier(name))?;
+            writeln!(writer, "    }}")?;
+            writeln!(writer, "}}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIRS: [KerningPair; 2] = [
+        KerningPair { left: 'A', right: 'V', adjustment: -2 },
+        KerningPair { left: 'T', right: 'é', adjustment: 1 }
+    ];
+
+    fn write(format: KerningFormat, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_kerning(&PAIRS, format, name, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn binary_layout() {
+        let mut expected = b"EJFK\x01".to_vec();
+        expected.extend([2, 0, 0, 0]);
+        expected.extend([0x41, 0, 0, 0, 0x56, 0, 0, 0, 0xfe, 0xff]);
+        expected.extend([0x54, 0, 0, 0, 0xe9, 0, 0, 0, 0x01, 0x00]);
+        assert_eq!(write(KerningFormat::Binary, "Roboto16"), expected);
+    }
+
+    #[test]
+    fn c_array() {
+        assert_eq!(String::from_utf8(write(KerningFormat::C, "Roboto-16")).unwrap(), "\
+// Kerning of the font Roboto-16, in pixels, generated by ejf-utils.
+#include <stdint.h>
+
+static const struct { uint32_t left; uint32_t right; int16_t adjustment; } Roboto_16_kerning[] = {
+    { 0x41, 0x56, -2 },
+    { 0x54, 0xe9, 1 },
+    { 0, 0, 0 }
+};
+");
+    }
+
+    #[test]
+    fn java_class() {
+        assert_eq!(String::from_utf8(write(KerningFormat::Java, "com.mycompany.Roboto16Kerning")).unwrap(), "\
+// Kerning of the font, in pixels, generated by ejf-utils.
+package com.mycompany;
+
+public final class Roboto16Kerning {
+
+    /** The left character, the right character and the adjustment of every pair. */
+    public static final int[] PAIRS = {
+        0x41, 0x56, -2,
+        0x54, 0xe9, 1,
+    };
+
+    private Roboto16Kerning() {
+    }
+}
+");
+    }
+
+    #[test]
+    fn java_packages() {
+        let class_name = |path: &str| java_class_name(Path::new(path), "Roboto16Kerning");
+        assert_eq!(class_name("src/main/java/com/mycompany/Roboto16Kerning.java"), "com.mycompany.Roboto16Kerning");
+        assert_eq!(class_name("java/src/java/Roboto16Kerning.java"), "Roboto16Kerning");
+        assert_eq!(class_name("src/Roboto16Kerning.java"), "Roboto16Kerning");
+        assert_eq!(class_name("Roboto16Kerning.java"), "Roboto16Kerning");
+    }
+}
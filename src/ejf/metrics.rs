@@ -4,11 +4,12 @@ use super::Error;
 
 pub struct Metrics {
     pub ascent: u16,
+    #[allow(dead_code)]
     pub descent: u16,
     pub height: u32
 }
 
-pub fn determine_metrics_from_font(face: &Face) -> Result<Metrics, Error> {
+pub fn determine_metrics_from_font<B>(face: &Face<B>) -> Result<Metrics, Error> {
     let metrics = face.size_metrics().ok_or(Error::MetricsError);
     let y_scale = metrics?.y_scale as f32 / 65536.0; // from fixed
     let ascender = ((face.ascender() as f32 * y_scale) as i32) >> 6;
@@ -20,12 +21,13 @@ pub fn determine_metrics_from_font(face: &Face) -> Result<Metrics, Error> {
     })
 }
 
-pub fn determine_metrics_from_render(face: &Face, chars: &[u8]) -> Result<Metrics, Error> {    
+#[allow(dead_code)]
+pub fn determine_metrics_from_render<B>(face: &Face<B>, chars: &[u8]) -> Result<Metrics, Error> {    
     let mut max_ascent: u16 = 0;
     let mut max_descent: u16 = 0;
     for code in chars {
         face.load_char(*code as usize, LoadFlag::RENDER)
-            .expect("Unable to load character.");
+            .map_err(|source| Error::GlyphLoadError { ch: *code as char, source })?;
         let glyph = face.glyph();
         let height = (glyph.metrics().height >> 6) as i32;
         let top = glyph.bitmap_top();
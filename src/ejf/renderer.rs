@@ -1,8 +1,12 @@
+#[cfg(feature = "print-characters")]
 use viuer::Config;
 use core::{cmp::max, cmp::min};
-use image::{DynamicImage, ImageBuffer};
+use std::collections::HashMap;
+use image::{imageops, DynamicImage, GrayImage, ImageBuffer};
 use freetype::{Face, Bitmap, face::LoadFlag};
 
+use super::Error;
+
 const DEBUG: bool = false;
 
 pub struct RenderConfig {
@@ -10,20 +14,42 @@ pub struct RenderConfig {
     pub left_spacing: u8,
     pub right_spacing: u8,
     pub max_ascent: u16,
+    /// Width of the space character, measured from the font if not set.
+    pub space_width: Option<u32>,
+    /// How U+0000 is rendered, the first column of the missing glyph of the font if not set.
+    pub fallback: Option<FallbackGlyph>,
+    /// Glyphs chosen by the OpenType features instead of the default glyph of a character.
+    pub glyphs: HashMap<char, u32>
+}
+
+/// The glyph of U+0000, along with its settings.
+pub enum FallbackGlyph {
+    Invisible { width: u32 },
+    Box { width: Option<u32> },
+    Replacement,
+    Image(GrayImage)
 }
 
-pub fn get_pixels(bitmap: Bitmap, config: RenderConfig, offset_y: i32, max_width: Option<usize>) -> DynamicImage {    
-    let char_width = if max_width.is_none() { bitmap.width() as usize } else { max_width.unwrap() };
+/// A rendered character.
+pub struct Glyph {
+    pub image: DynamicImage,
+    /// Whether part of the character did not fit in the image and was cut off.
+    pub clipped: bool
+}
+
+pub fn get_pixels(bitmap: Bitmap, config: RenderConfig, offset_y: i32, max_width: Option<usize>) -> Glyph {
+    let char_width = max_width.unwrap_or(bitmap.width() as usize);
     let image_height = config.total_height as i32;
 
     let mut image_width: i32 = (config.left_spacing as i32) + (char_width as i32) + (config.right_spacing as i32);
-    if max_width.is_some() {
-        image_width = min(image_width, max_width.unwrap().try_into().unwrap())
+    if let Some(max_width) = max_width {
+        image_width = min(image_width, i32::try_from(max_width).unwrap_or(i32::MAX))
     }
     let image_width: i32 = max(1, image_width); // 0px width images are not allowed.
     let offset_x = config.left_spacing as i32;
     let mut figure = ImageBuffer::new(image_width as u32, image_height as u32);
-    
+    let mut clipped = false;
+
     for cx in 0..char_width {
         for cy in 0..bitmap.rows() as usize {
             let pixel = [ bitmap.buffer()[cy * char_width + cx] ];
@@ -32,19 +58,87 @@ pub fn get_pixels(bitmap: Bitmap, config: RenderConfig, offset_y: i32, max_width
 
             if dest_x < image_width && dest_y >= 0 && dest_y < image_height {
                 figure[(dest_x as u32, dest_y as u32)] = image::Luma(pixel);
+            } else if pixel[0] > 0 && max_width.is_none() {
+                // Cutting a character to a maximum width is intended, so it's not reported.
+                clipped = true;
             }
         }
     }
 
     let mut image = DynamicImage::ImageLuma8(figure);
     image.invert();
-    image 
+    Glyph { image, clipped }
+}
+
+/// Load the glyph of a character, the one chosen by the OpenType features if any.
+fn load_glyph<B>(face: &Face<B>, ch: char, config: &RenderConfig, flags: LoadFlag) -> Result<(), Error> {
+    match config.glyphs.get(&ch) {
+        Some(glyph_id) => face.load_glyph(*glyph_id, flags),
+        None => face.load_char(ch as usize, flags)
+    }.map_err(|source| Error::GlyphLoadError { ch, source })
+}
+
+/// The advance of a character, that is the distance to the next one, in pixels.
+fn advance_width<B>(face: &Face<B>, ch: char, config: &RenderConfig) -> Result<u32, Error> {
+    load_glyph(face, ch, config, LoadFlag::DEFAULT)?;
+    Ok(max(0, (face.glyph().advance().x + 32) >> 6) as u32)
+}
+
+/// Whitespace has no bitmap and only an advance, so it's rendered as a blank image as wide as the advance.
+fn render_whitespace<B>(face: &Face<B>, ch: char, config: &RenderConfig) -> Result<Glyph, Error> {
+    let width = match (ch, config.space_width) {
+        (' ', Some(space_width)) => space_width,
+        _ => advance_width(face, ch, config)?
+    };
+
+    Ok(Glyph { image: DynamicImage::ImageLuma8(blank_image(width, config.total_height)), clipped: false })
 }
 
-pub fn render_single_character(face: &Face, ch: char, config: &RenderConfig) -> DynamicImage {    
+fn blank_image(width: u32, height: u32) -> GrayImage {
+    ImageBuffer::from_pixel(max(1, width), height, image::Luma([u8::MAX]))
+}
+
+fn render_fallback<B>(face: &Face<B>, fallback: &FallbackGlyph, config: &RenderConfig) -> Result<Glyph, Error> {
+    let height = config.total_height;
+    let (image, clipped) = match fallback {
+        FallbackGlyph::Invisible { width } => (blank_image(*width, height), false),
+        FallbackGlyph::Box { width } => {
+            // A 1px outline from the top of the font to the baseline, with 1px of space on each side.
+            let width = max(3, width.unwrap_or(height / 2));
+            let bottom = min(height, max(2, config.max_ascent as u32)) - 1;
+            let mut image = blank_image(width, height);
+            for x in 1..width - 1 {
+                for y in 0..=bottom {
+                    if x == 1 || x == width - 2 || y == 0 || y == bottom {
+                        image[(x, y)] = image::Luma([0]);
+                    }
+                }
+            }
+            (image, false)
+        },
+        FallbackGlyph::Replacement => return render_single_character(face, '\u{FFFD}', config),
+        FallbackGlyph::Image(fallback_image) => {
+            // Centered vertically, and cut off if taller than the font.
+            let mut image = blank_image(fallback_image.width(), height);
+            let offset_y = (height as i64 - fallback_image.height() as i64) / 2;
+            imageops::overlay(&mut image, fallback_image, 0, offset_y);
+            (image, fallback_image.height() > height)
+        }
+    };
+    Ok(Glyph { image: DynamicImage::ImageLuma8(image), clipped })
+}
+
+pub fn render_single_character<B>(face: &Face<B>, ch: char, config: &RenderConfig) -> Result<Glyph, Error> {
+    if ch.is_whitespace() {
+        return render_whitespace(face, ch, config);
+    }
+
+    if let (Some(fallback), '\0') = (&config.fallback, ch) {
+        return render_fallback(face, fallback, config);
+    }
+
     // Try to render a single character.
-    face.load_char(ch as usize, LoadFlag::RENDER)
-        .expect("Unable to load one of the characters for rendering.");
+    load_glyph(face, ch, config, LoadFlag::RENDER)?;
 
     let glyph = face.glyph();
     let metrics = glyph.metrics();
@@ -60,28 +154,42 @@ pub fn render_single_character(face: &Face, ch: char, config: &RenderConfig) ->
     }
 
     // Get the pixels of that single character.
-    let offset_y = config.max_ascent as i32 - (glyph.bitmap_top() as i32);
+    let offset_y = config.max_ascent as i32 - glyph.bitmap_top();
     let max_width = if ch == char::from_u32(0x00).unwrap() { Option::Some(1) } else { Option::None }; 
-    let img = get_pixels(glyph.bitmap(), RenderConfig {
+    Ok(get_pixels(glyph.bitmap(), RenderConfig {
         left_spacing,
         right_spacing,
         max_ascent: config.max_ascent,
-        total_height: config.total_height
-    }, offset_y, max_width);    
+        total_height: config.total_height,
+        space_width: config.space_width,
+        fallback: None,
+        glyphs: HashMap::new()
+    }, offset_y, max_width))
+}
 
-    img
+/// Center the glyph in an image of the given width, cutting it off if it's wider.
+pub fn fit_width(glyph: Glyph, width: u32) -> Glyph {
+    let image = glyph.image.to_luma8();
+    let mut fitted = blank_image(width, image.height());
+    let offset_x = (width as i64 - image.width() as i64) / 2;
+    imageops::overlay(&mut fitted, &image, offset_x, 0);
+    Glyph {
+        image: DynamicImage::ImageLuma8(fitted),
+        clipped: glyph.clipped || image.width() > max(1, width)
+    }
 }
 
-pub fn get_char_width(face: &Face, ch: char, config: &RenderConfig) -> u32 {
-    render_single_character(&face, ch, config).width()
+pub fn get_char_width<B>(face: &Face<B>, ch: char, config: &RenderConfig) -> Result<u32, Error> {
+    Ok(render_single_character(face, ch, config)?.image.width())
 }
 
+#[cfg(feature = "print-characters")]
 pub fn print_character(img: &DynamicImage) {
     let config = Config {
         absolute_offset: false,
         ..Default::default()
     };
 
-    viuer::print(&img, &config)
+    viuer::print(img, &config)
         .expect("Image printing failed.");
 }
\ No newline at end of file
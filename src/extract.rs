@@ -0,0 +1,356 @@
+// Extracts the characters used by an application from its source and translation files, so that fonts
+// only contain the characters that are actually displayed.
+
+use std::{collections::BTreeSet, fs, path::{Path, PathBuf}, string::FromUtf8Error};
+use quick_xml::{events::Event, Reader};
+
+use crate::ejf::Error;
+
+/// Find the files matching the given glob patterns (e.g. `i18n/*.json` or `src/**/*.java`), relative to the
+/// current directory. Every pattern must match at least one file, to catch typos.
+pub fn matching_files(patterns: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let paths = glob::glob(pattern)
+            .map_err(|source| Error::PatternError { pattern: pattern.clone(), source })?;
+
+        let num_files = files.len();
+        for path in paths {
+            let path = path.map_err(|e| {
+                let path = e.path().to_string_lossy().to_string();
+                Error::io(&path, e.into())
+            })?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+
+        if files.len() == num_files {
+            return Err(Error::NoMatchError { pattern: pattern.clone() });
+        }
+    }
+    Ok(files)
+}
+
+/// The characters used in the files matching the given glob patterns.
+pub fn chars_from(patterns: &[String]) -> Result<BTreeSet<char>, Error> {
+    let mut chars = BTreeSet::new();
+    for file in matching_files(patterns)? {
+        for text in extract_text(&file)? {
+            chars.extend(text.chars());
+        }
+    }
+    Ok(chars)
+}
+
+/// Extract the texts displayed by the application from a file, depending on its format:
+///
+/// * JSON (`.json`): every string value.
+/// * gettext (`.po`, `.pot`): the translated strings (`msgstr`), or the original ones if not translated.
+/// * XML (`.xml`), such as Android `strings.xml`: the content of the `<string>` and `<item>` elements.
+/// * Java properties (`.properties`), including NLS files: the values.
+/// * Any other file is considered source code, and the content of its string literals is extracted.
+///
+/// Java properties are read as UTF-8, or as ISO-8859-1 if they are not valid UTF-8, every other file must be
+/// encoded in UTF-8.
+pub fn extract_text(path: &Path) -> Result<Vec<String>, Error> {
+    let display_path = path.to_string_lossy().to_string();
+    let data = fs::read(path).map_err(|e| Error::io(&display_path, e))?;
+    let extract_error = |source: Box<dyn std::error::Error + Send + Sync>| Error::ExtractError { path: display_path.clone(), source };
+
+    let extension = path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let content = decode(data, &extension).map_err(|e| extract_error(e.into()))?;
+
+    match extension.as_str() {
+        "json" => {
+            let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| extract_error(e.into()))?;
+            let mut texts = Vec::new();
+            json_strings(&value, &mut texts);
+            Ok(texts)
+        },
+        "po" | "pot" => Ok(po_strings(&content)),
+        "xml" => xml_strings(&content).map_err(|e| extract_error(e.into())),
+        "properties" => Ok(properties_values(&content)),
+        _ => Ok(string_literals(&content))
+    }
+}
+
+/// The text of a file, read as UTF-8. Like `PropertyResourceBundle`, Java properties which are not valid UTF-8
+/// are read as ISO-8859-1 instead, where every byte is the code point of a character.
+fn decode(data: Vec<u8>, extension: &str) -> Result<String, FromUtf8Error> {
+    match (String::from_utf8(data), extension) {
+        (Err(error), "properties") => Ok(error.into_bytes().into_iter().map(char::from).collect()),
+        (result, _) => result
+    }
+}
+
+fn json_strings(value: &serde_json::Value, texts: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(text) => texts.push(text.clone()),
+        serde_json::Value::Array(values) => values.iter().for_each(|value| json_strings(value, texts)),
+        serde_json::Value::Object(entries) => entries.values().for_each(|value| json_strings(value, texts)),
+        _ => ()
+    }
+}
+
+/// The texts of a gettext file: the translations (`msgstr`) of its entries, or their original strings (`msgid`
+/// and `msgid_plural`) when they are not translated, as in a `.pot` template. Strings can span several lines
+/// (`msgstr ""` followed by `"..."`), and the header (the entry with an empty `msgid`) is left out.
+fn po_strings(content: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    // The keywords of the current entry along with their strings.
+    let mut entry: Vec<(&str, String)> = Vec::new();
+    let mut in_string = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('"') {
+            if let (true, Some((_, text))) = (in_string, entry.last_mut()) {
+                text.extend(string_literals(line));
+            }
+            continue;
+        }
+
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        in_string = keyword.starts_with("msg");
+        if !in_string {
+            continue;
+        }
+
+        // A new entry starts with its context or its msgid.
+        let is_new_entry = keyword == "msgctxt" || keyword == "msgid";
+        if is_new_entry && entry.iter().any(|(keyword, _)| keyword.starts_with("msgstr")) {
+            push_po_entry(&entry, &mut texts);
+            entry.clear();
+        }
+        entry.push((keyword, string_literals(&line[keyword.len()..]).concat()));
+    }
+    push_po_entry(&entry, &mut texts);
+    texts
+}
+
+fn push_po_entry(entry: &[(&str, String)], texts: &mut Vec<String>) {
+    if entry.iter().any(|(keyword, text)| *keyword == "msgid" && text.is_empty()) {
+        return;
+    }
+
+    let is_msgstr = |keyword: &str| keyword.starts_with("msgstr");
+    let is_translated = entry.iter().any(|(keyword, text)| is_msgstr(keyword) && !text.is_empty());
+    texts.extend(entry.iter()
+        .filter(|(keyword, text)| !text.is_empty() && match is_translated {
+            true => is_msgstr(keyword),
+            false => *keyword == "msgid" || *keyword == "msgid_plural"
+        })
+        .map(|(_, text)| text.clone()));
+}
+
+fn xml_strings(content: &str) -> Result<Vec<String>, quick_xml::Error> {
+    let mut reader = Reader::from_str(content);
+    let mut buf = Vec::new();
+    let mut texts = Vec::new();
+    let mut depth = 0;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(element) if matches!(element.name(), b"string" | b"item") => depth += 1,
+            Event::End(element) if matches!(element.name(), b"string" | b"item") => depth -= 1,
+            Event::Text(text) if depth > 0 => texts.push(unescape(&text.unescape_and_decode(&reader)?)),
+            Event::CData(text) if depth > 0 => texts.push(String::from_utf8_lossy(&text).to_string()),
+            Event::Eof => break,
+            _ => ()
+        }
+        buf.clear();
+    }
+    Ok(texts)
+}
+
+/// The values of a Java properties file, where a line ending with `\` continues on the next one.
+fn properties_values(content: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut logical_line = String::new();
+    for line in content.lines() {
+        let line = line.trim_start();
+        if logical_line.is_empty() && (line.starts_with('#') || line.starts_with('!')) {
+            continue;
+        }
+
+        // A line ends with an escaped backslash (`\\`) if the number of trailing backslashes is even.
+        let trailing_backslashes = line.len() - line.trim_end_matches('\\').len();
+        if trailing_backslashes % 2 == 1 {
+            logical_line.push_str(&line[..line.len() - 1]);
+            continue;
+        }
+        logical_line.push_str(line);
+        texts.extend(property_value(&logical_line));
+        logical_line.clear();
+    }
+
+    // The last line may still be continued at the end of the file.
+    texts.extend(property_value(&logical_line));
+    texts
+}
+
+/// The value of a line of a properties file, if it has one. The key ends at the first unescaped '=', ':' or
+/// whitespace.
+fn property_value(line: &str) -> Option<String> {
+    let mut escaped = false;
+    let (index, _) = line.char_indices().find(|(_, ch)| {
+        let is_separator = !escaped && (*ch == '=' || *ch == ':' || ch.is_whitespace());
+        escaped = !escaped && *ch == '\\';
+        is_separator
+    })?;
+    let value = line[index + 1..].trim_start_matches(|ch: char| ch == '=' || ch == ':' || ch.is_whitespace());
+    Some(unescape(value))
+}
+
+/// The content of the string literals, between single or double quotes. Literals cannot span several lines,
+/// so that an apostrophe in a comment does not swallow the rest of the file.
+fn string_literals(content: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    for line in content.lines() {
+        let mut chars = line.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '"' && ch != '\'' {
+                continue;
+            }
+
+            let mut literal = String::new();
+            let mut escaped = false;
+            let mut closed = false;
+            for next in chars.by_ref() {
+                if next == ch && !escaped {
+                    closed = true;
+                    break;
+                }
+                escaped = !escaped && next == '\\';
+                literal.push(next);
+            }
+
+            if closed {
+                texts.push(unescape(&literal));
+            }
+        }
+    }
+    texts
+}
+
+/// Resolve the escape sequences common to most languages: `\n`, `\t`, `\uXXXX`, `\u{XXXX}`, `\xXX`, and a
+/// backslash followed by any other character standing for that character.
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        let hex_digits = |chars: &mut std::iter::Peekable<std::str::Chars>, count: usize| -> Option<char> {
+            let digits: String = (0..count).map_while(|_| chars.next_if(char::is_ascii_hexdigit)).collect();
+            u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+        };
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('u') if chars.next_if_eq(&'{').is_some() => {
+                result.extend(hex_digits(&mut chars, 6));
+                chars.next_if_eq(&'}');
+            },
+            Some('u') => result.extend(hex_digits(&mut chars, 4)),
+            Some('x') => result.extend(hex_digits(&mut chars, 2)),
+            Some(other) => result.push(other),
+            None => ()
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn po_plurals_and_multiline_strings() {
+        let content = r#"
+# A "comment"
+msgid "One file"
+msgid_plural "%d files"
+msgstr[0] "Une fichier"
+msgstr[1] "%d fichiers"
+
+msgid "Two lines"
+msgstr ""
+"Première ligne\n"
+"Deuxième ligne"
+"#;
+        assert_eq!(po_strings(content), ["Une fichier", "%d fichiers", "Première ligne\nDeuxième ligne"]);
+    }
+
+    #[test]
+    fn po_untranslated_entries_and_templates() {
+        let content = r#"
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+msgctxt "menu"
+msgid "Open"
+msgstr ""
+
+msgid "One file"
+msgid_plural "%d files"
+msgstr[0] ""
+msgstr[1] ""
+
+msgid "Close"
+msgstr "Fermer"
+"#;
+        assert_eq!(po_strings(content), ["Open", "One file", "%d files", "Fermer"]);
+    }
+
+    #[test]
+    fn properties_values_and_continuations() {
+        let content = "# Comment: ignored\n\
+            title = Grüße\n\
+            message:First \\\n    second\n\
+            path=C:\\\\\n\
+            key\\=with\\:separators = Value\n\
+            escaped=\\u00e9t\\u00E9\n";
+        assert_eq!(properties_values(content), ["Grüße", "First second", "C:\\", "Value", "été"]);
+    }
+
+    #[test]
+    fn properties_are_latin1_and_others_utf8() {
+        assert_eq!(decode(b"k=caf\xe9".to_vec(), "properties").unwrap(), "k=café");
+        assert_eq!(decode("k=café".as_bytes().to_vec(), "json").unwrap(), "k=café");
+        assert!(decode(b"k=caf\xe9".to_vec(), "json").is_err());
+    }
+
+    #[test]
+    fn utf8_properties() {
+        assert_eq!(decode("title=Grüße".as_bytes().to_vec(), "properties").unwrap(), "title=Grüße");
+    }
+
+    #[test]
+    fn string_literals_with_escapes() {
+        let content = r#"label.setText("Héllo \"world\"\n"); char c = '\u00e9'; // it's a comment"#;
+        assert_eq!(string_literals(content), ["Héllo \"world\"\n", "é"]);
+    }
+
+    #[test]
+    fn unescape_sequences() {
+        assert_eq!(unescape(r"\u00e9\u{1F600}\x41\t\q"), "é😀A\tq");
+    }
+
+    #[test]
+    fn malformed_input() {
+        // Invalid escapes are dropped instead of failing, and unterminated literals are ignored.
+        assert_eq!(unescape(r"a\uZZZZb\xg"), "aZZZZbg");
+        assert_eq!(unescape(r"\u{110000}end\"), "end");
+        assert_eq!(string_literals("\"unterminated\nnext 'line'"), ["line"]);
+        assert_eq!(po_strings("msgstr \"open"), Vec::<String>::new());
+        assert_eq!(properties_values("no_value\nkey = dangling\\"), ["dangling"]);
+        assert!(xml_strings("<resources><string>Text</item></resources>").is_err());
+    }
+}
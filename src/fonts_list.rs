@@ -0,0 +1,174 @@
+// Converts between manifests and the `.fonts.list` files of the MicroEJ SDK, which list the fonts of an
+// application as `path:ranges:bpp`, e.g.:
+//
+//   com/mycompany/Roboto16.ejf:0x20-0x7e,0xa0-0xff:4
+//
+// Unlike in `char_range`, the ranges of a `.fonts.list` include their end.
+
+use std::fmt;
+
+use crate::{char_range::{closest_name, format_ranges, unicode}, ejf::Error};
+
+/// The number of bits per pixel the MicroEJ SDK can convert a font to.
+const VALID_BPP: [u8; 4] = [1, 2, 4, 8];
+
+/// A single line of a `.fonts.list` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontsListEntry {
+    /// Path of the .ejf, relative to the resources of the application.
+    pub path: String,
+    /// The characters to embed, either ranges (`0x20-0x7e`) or the names of ranges known by the SDK.
+    pub range: Option<String>,
+    pub bpp: Option<u8>
+}
+
+impl fmt::Display for FontsListEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        match (&self.range, self.bpp) {
+            (Some(range), Some(bpp)) => write!(f, ":{}:{}", range, bpp),
+            (Some(range), None) => write!(f, ":{}", range),
+            (None, Some(bpp)) => write!(f, "::{}", bpp),
+            (None, None) => Ok(())
+        }
+    }
+}
+
+fn is_char_code(value: &str) -> bool {
+    let value = value.trim();
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(digits) => u32::from_str_radix(digits, 16).is_ok(),
+        None => value.parse::<u32>().is_ok()
+    }
+}
+
+fn is_char_codes(item: &str) -> bool {
+    match item.split_once('-') {
+        Some((start, end)) => is_char_code(start) && is_char_code(end),
+        None => is_char_code(item)
+    }
+}
+
+/// The first name of a range which is not a Unicode block, the only names `to_char_range` can convert.
+fn unknown_range_name(range: &str) -> Option<&str> {
+    range.split(',')
+        .map(str::trim)
+        .find(|item| !item.is_empty() && !is_char_codes(item) && unicode::block(item).is_none())
+}
+
+/// Parse the content of a `.fonts.list` file, ignoring empty lines and comments (`#`). The names of ranges must
+/// be the ones of Unicode blocks.
+pub fn parse(content: &str) -> Result<Vec<FontsListEntry>, Error> {
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |message: String| Error::FontsListError { line: index + 1, message };
+        let parts: Vec<&str> = line.split(':').map(str::trim).collect();
+        if parts.len() > 3 {
+            return Err(error(format!("Expected 'path:ranges:bpp' but found '{}'", line)));
+        }
+
+        let path = parts[0];
+        if path.is_empty() {
+            return Err(error("Missing the path of the font".to_string()));
+        }
+
+        let range = parts.get(1).filter(|range| !range.is_empty());
+        if let Some(name) = range.and_then(|range| unknown_range_name(range)) {
+            let message = match closest_name(name, unicode::block_names()) {
+                Some(suggestion) => format!("Unknown range '{}', the closest Unicode block is '{}'", name, suggestion),
+                None => format!("Unknown range '{}', expected character codes or the name of a Unicode block", name)
+            };
+            return Err(error(message));
+        }
+
+        let bpp = match parts.get(2).filter(|bpp| !bpp.is_empty()) {
+            Some(bpp) => match bpp.parse::<u8>() {
+                Ok(bpp) if VALID_BPP.contains(&bpp) => Some(bpp),
+                _ => return Err(error(format!("Invalid number of bits per pixel '{}', expected 1, 2, 4 or 8", bpp)))
+            },
+            None => None
+        };
+
+        entries.push(FontsListEntry {
+            path: path.to_string(),
+            range: range.map(|range| range.to_string()),
+            bpp
+        });
+    }
+    Ok(entries)
+}
+
+/// Format a list of characters as the ranges of a `.fonts.list` entry, e.g. `0x20-0x7e,0xa0`.
+pub fn format_range(chars: &[char]) -> String {
+    format_ranges(chars, true)
+}
+
+/// Convert the ranges of a `.fonts.list` entry to a `char_range` descriptor. The ranges are converted to
+/// inclusive ones (`0x20..=0x7e`), and the names of ranges to Unicode blocks (`block:name`).
+pub fn to_char_range(range: &str) -> String {
+    range.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.split_once('-') {
+            Some((start, end)) if is_char_code(start) && is_char_code(end) => format!("{}..={}", start.trim(), end.trim()),
+            _ if is_char_code(item) => item.to_string(),
+            _ => format!("block:{}", item)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(content: &str) -> (usize, String) {
+        match parse(content) {
+            Err(Error::FontsListError { line, message }) => (line, message),
+            result => panic!("Expected a .fonts.list error, got {:?}", result)
+        }
+    }
+
+    #[test]
+    fn entries_are_parsed() {
+        let content = "# Fonts of the application\n\
+            \n\
+            com/mycompany/Roboto16.ejf:0x20-0x7e,0xa0:4\n\
+            \x20 fonts/Title.ejf : : 2\n\
+            fonts/Plain.ejf\n";
+        let entries = parse(content).unwrap();
+        assert_eq!(entries, [
+            FontsListEntry { path: "com/mycompany/Roboto16.ejf".to_string(), range: Some("0x20-0x7e,0xa0".to_string()), bpp: Some(4) },
+            FontsListEntry { path: "fonts/Title.ejf".to_string(), range: None, bpp: Some(2) },
+            FontsListEntry { path: "fonts/Plain.ejf".to_string(), range: None, bpp: None }
+        ]);
+        assert_eq!(entries.iter().map(ToString::to_string).collect::<Vec<String>>(),
+            ["com/mycompany/Roboto16.ejf:0x20-0x7e,0xa0:4", "fonts/Title.ejf::2", "fonts/Plain.ejf"]);
+    }
+
+    #[test]
+    fn invalid_entries() {
+        assert_eq!(parse_error("a.ejf:0x20:4\nb.ejf:0x20:3"), (2, "Invalid number of bits per pixel '3', expected 1, 2, 4 or 8".to_string()));
+        assert_eq!(parse_error(":0x20:4"), (1, "Missing the path of the font".to_string()));
+        assert_eq!(parse_error("a.ejf:0x20:4:1"), (1, "Expected 'path:ranges:bpp' but found 'a.ejf:0x20:4:1'".to_string()));
+    }
+
+    #[test]
+    fn range_names_must_be_unicode_blocks() {
+        assert!(parse("a.ejf:0x20-0x7e,Basic Latin,latin_1_supplement:4").is_ok());
+        assert_eq!(parse_error("a.ejf:0x20-0x7e,Basic Latn:4"), (1, "Unknown range 'Basic Latn', the closest Unicode block is 'Basic Latin'".to_string()));
+        assert_eq!(parse_error("a.ejf:0x20-zz"), (1, "Unknown range '0x20-zz', expected character codes or the name of a Unicode block".to_string()));
+    }
+
+    #[test]
+    fn ranges_include_their_end() {
+        assert_eq!(format_range(&['c', 'a', 'b', 'x', 'a']), "0x61-0x63,0x78");
+        assert_eq!(to_char_range("0x20-0x7e, 0xa0,160 - 0xff"), "0x20..=0x7e, 0xa0, 160..=0xff");
+        assert_eq!(to_char_range("Basic Latin,0x41"), "block:Basic Latin, 0x41");
+    }
+}
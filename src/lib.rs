@@ -0,0 +1,12 @@
+//! Generates .ejf font files for the MicroUI embedded platform from TrueType/OpenType fonts.
+//!
+//! Fonts are built either one at a time with a [`FontBuilder`], or from the entries of a manifest, read with
+//! [`manifest::load_config`], with [`ejf::build_ejf`]. The `ejf-utils` command line tool is a thin wrapper around this library.
+
+pub mod char_range;
+pub mod ejf;
+pub mod extract;
+pub mod fonts_list;
+pub mod manifest;
+
+pub use ejf::{FontBuilder, FontSource, Error};
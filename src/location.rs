@@ -0,0 +1,174 @@
+// Locates the options of the fonts and charsets in the manifest, so that errors can point at the line declaring
+// them.
+
+use std::{fs, path::{Path, PathBuf}};
+
+use ejf_utils::ejf::EjfConfig;
+
+/// The section of the manifest an option is declared in.
+pub enum Section<'a> {
+    Font(&'a EjfConfig),
+    Charset(&'a str)
+}
+
+/// Where an option is declared.
+pub struct Location {
+    pub file: PathBuf,
+    /// Starting at 1.
+    pub line_number: usize,
+    pub line: String,
+    /// The column of every character of the value in the line, starting at 1, followed by the one of the closing
+    /// quote. Escape sequences take several columns.
+    pub columns: Vec<usize>
+}
+
+impl Location {
+    /// The column of the given character of the value and the number of columns the next `length` characters take.
+    pub fn span(&self, index: usize, length: usize) -> (usize, usize) {
+        let last = self.columns.len() - 1;
+        let (start, end) = (index.min(last), (index + length).min(last));
+        (self.columns[start], (self.columns[end] - self.columns[start]).max(1))
+    }
+}
+
+/// The sections of a TOML file along with their header (empty for the top level), as their lines with their
+/// index.
+fn sections(content: &str) -> Vec<(&str, Vec<(usize, &str)>)> {
+    let mut sections = vec![("", Vec::new())];
+    for (index, line) in content.lines().enumerate() {
+        match line.trim_start().starts_with('[') {
+            true => sections.push((line.trim(), Vec::new())),
+            false => sections.last_mut().unwrap().1.push((index, line))
+        }
+    }
+    sections
+}
+
+/// Whether the section with the given header and content is the one of the font or charset.
+fn is_section(section: &Section, header: &str, table: &toml::Value) -> bool {
+    match section {
+        Section::Font(font) => {
+            let file_name = |path: &str| Path::new(path).file_name().map(|name| name.to_os_string());
+            header == "[[font]]" && table.get("output").and_then(toml::Value::as_str).and_then(file_name) == file_name(&font.output)
+        },
+        Section::Charset(name) => header.strip_prefix("[charset.")
+            .and_then(|header| header.strip_suffix(']'))
+            .is_some_and(|header| header.trim().trim_matches(['"', '\'']) == *name)
+    }
+}
+
+/// The offset (in characters, from the opening quote) of every character of a TOML string, followed by the one
+/// of the closing quote. `None` for multi-line strings, or if the string is not terminated.
+fn string_offsets(literal: &str) -> Option<Vec<usize>> {
+    if literal.starts_with("\"\"\"") || literal.starts_with("'''") {
+        return None;
+    }
+
+    let mut chars = literal.chars().enumerate();
+    let quote = chars.next()?.1;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+
+    let mut offsets = Vec::new();
+    while let Some((offset, ch)) = chars.next() {
+        if ch == quote {
+            offsets.push(offset);
+            return Some(offsets);
+        }
+        offsets.push(offset);
+
+        // Literal strings (between single quotes) do not have escape sequences.
+        if quote == '"' && ch == '\\' {
+            let length = match chars.next()?.1 {
+                'u' => 4,
+                'U' => 8,
+                _ => 0
+            };
+            for _ in 0..length {
+                chars.next()?;
+            }
+        }
+    }
+    None
+}
+
+/// Find where the option of the font or charset has the given value, in the manifest or the files it includes.
+pub fn find_option(files: &[PathBuf], section: &Section, option: &str, value: &str) -> Option<Location> {
+    files.iter().find_map(|file| {
+        let content = fs::read_to_string(file).ok()?;
+        sections(&content).into_iter().find_map(|(header, lines)| {
+            let text: Vec<&str> = lines.iter().map(|(_, line)| *line).collect();
+            let table: toml::Value = text.join("\n").parse().ok()?;
+            if !is_section(section, header, &table) || table.get(option)?.as_str() != Some(value) {
+                return None;
+            }
+
+            lines.iter().find_map(|(index, line)| {
+                let (key, literal) = line.split_once('=')?;
+                if key.trim().trim_matches(['"', '\'']) != option {
+                    return None;
+                }
+
+                let literal_start = line.len() - literal.trim_start().len();
+                let offsets = string_offsets(literal.trim_start())?;
+                if offsets.len() != value.chars().count() + 1 {
+                    return None;
+                }
+
+                let literal_column = line[..literal_start].chars().count() + 1;
+                Some(Location {
+                    file: file.clone(),
+                    line_number: index + 1,
+                    line: line.to_string(),
+                    columns: offsets.into_iter().map(|offset| literal_column + offset).collect()
+                })
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_of_escaped_strings() {
+        assert_eq!(string_offsets(r#""0x41, zz""#), Some(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]));
+        assert_eq!(string_offsets(r#""\"ÄÖ\", zz" # comment"#), Some(vec![1, 3, 4, 5, 7, 8, 9, 10, 11]));
+        assert_eq!(string_offsets(r#""\u00c4, \U0001F600""#), Some(vec![1, 7, 8, 9, 19]));
+        assert_eq!(string_offsets(r#"'C:\path'"#), Some(vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(string_offsets(r#""unterminated"#), None);
+        assert_eq!(string_offsets(r#""""multi-line""""#), None);
+    }
+
+    #[test]
+    fn options_are_found_in_their_section() {
+        let path = std::env::temp_dir().join(format!("ejf-utils-location-{}.toml", std::process::id()));
+        fs::write(&path, r#"
+[charset.latin]
+range = "0x20-0x7f"
+
+[[font]]
+input = "a.ttf"
+output = "a.ejf"
+char_range = "0x41-0x5b, zz"
+
+[[font]]
+output = "b.ejf"
+char_range = "zz"
+ignore_char_range = "\"ÄÖ\", zz"
+"#).unwrap();
+
+        let files = [path.clone()];
+        let b = EjfConfig::new("b.ttf", "out/b.ejf", 16);
+        let location = find_option(&files, &Section::Font(&b), "char_range", "zz").unwrap();
+        assert_eq!((location.line_number, location.span(0, 2)), (12, (15, 2)));
+        let location = find_option(&files, &Section::Font(&b), "ignore_char_range", "\"ÄÖ\", zz").unwrap();
+        assert_eq!((location.line_number, location.span(0, 4), location.span(6, 2)), (13, (22, 6), (30, 2)));
+        let location = find_option(&files, &Section::Charset("latin"), "range", "0x20-0x7f").unwrap();
+        assert_eq!(location.line_number, 3);
+        assert!(find_option(&files, &Section::Font(&EjfConfig::new("c.ttf", "c.ejf", 16)), "char_range", "zz").is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fs, io, path::Path};
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+use ejf_utils::ejf::{EjfConfig, Error, build_char_list, build_monospace_list};
+
+/// Increased whenever the generator produces a different output for the same inputs, so that the fonts
+/// generated before the change are not considered up to date.
+const OUTPUT_REVISION: u32 = 3;
+
+/// Records, for every generated font, what it was generated from so that it can be skipped
+/// on the next build if nothing changed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub font: Vec<LockEntry>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    pub output: String,
+    /// Hash of the input font, the font configuration, its characters and the version of the tool.
+    pub fingerprint: String,
+    /// Hash of the generated .ejf, to detect fonts that were modified or deleted after generation.
+    pub checksum: String
+}
+
+impl Lockfile {
+    /// Read the lockfile at the given path, an unreadable or missing lockfile is treated as empty.
+    pub fn read(path: &Path) -> Lockfile {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|data| toml::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = toml::to_string(self).expect("Unable to serialize the lockfile.");
+        fs::write(path, data)
+    }
+
+    pub fn find(&self, output: &str) -> Option<&LockEntry> {
+        self.font.iter().find(|entry| entry.output == output)
+    }
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Determine the fingerprint of everything a font is generated from.
+pub fn fingerprint(config: &EjfConfig) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(OUTPUT_REVISION.to_le_bytes());
+    hasher.update(toml::to_string(config).expect("Unable to serialize the font configuration."));
+    // The characters also depend on the content of the files of chars_from, and on the charsets they reference.
+    let chars = build_char_list(config)?;
+    hasher.update(build_monospace_list(config, &chars)?.into_iter().collect::<String>());
+    hasher.update(chars.into_iter().collect::<String>());
+    hasher.update(fs::read(&config.input).map_err(|e| Error::io(&config.input, e))?);
+    if let Some(image) = &config.fallback_image {
+        hasher.update(fs::read(image).map_err(|e| Error::io(image, e))?);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn checksum(path: &str) -> io::Result<String> {
+    Ok(to_hex(&Sha256::digest(fs::read(path)?)))
+}
+
+/// Whether the font was already generated from the same fingerprint and was not modified since.
+pub fn is_up_to_date(entry: Option<&LockEntry>, fingerprint: &str) -> bool {
+    match entry {
+        Some(entry) => entry.fingerprint == fingerprint
+            && checksum(&entry.output).is_ok_and(|checksum| checksum == entry.checksum),
+        None => false
+    }
+}
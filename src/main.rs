@@ -1,25 +1,42 @@
-use std::{fs, process::exit, env::{args, set_current_dir}, path::Path, thread::{self, JoinHandle}};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use serde::{Deserialize};
+use std::{cmp::max, error::Error as _, fs::{self, File}, io::Cursor, num::NonZeroUsize, panic::{self, AssertUnwindSafe}, process::exit, env::set_current_dir, path::{self, Path, PathBuf}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-mod ejf;
-mod char_range;
-use char_range::char_range;
-use ejf::{EjfConfig, Error, build_ejf, EjfResult, get_font_name};
+mod cli;
+mod location;
+mod lockfile;
+mod report;
+mod watch;
+use ejf_utils::{char_range::{self, char_range_warnings, format_char_range, ParseError}, extract, fonts_list::{self, FontsListEntry}, manifest::load_config};
+use ejf_utils::ejf::{EjfConfig, EjfInfo, Error, FontBuilder, read_ejf, EjfResult, build_char_list, get_font_name};
+use cli::{Cli, Command, ExportArgs, ImportArgs, ManifestArgs, Output};
+use location::{find_option, Section};
+use lockfile::{Lockfile, LockEntry};
+use report::{FontReport, FontStatus};
+use watch::FileWatcher;
 
-#[derive(Debug, Deserialize)]
-struct Config {
-    font: Vec<EjfConfig>
+struct ThreadData<T> {
+    font: EjfConfig,
+    result: Result<T, Error>
 }
 
-struct ThreadData {
-    input: String,
-    output: String,
-    result: Result<EjfResult, Error>
+/// The outcome of building a single font, along with what it was built from.
+struct BuildOutcome {
+    result: EjfResult,
+    lock: LockEntry,
+    build_time: Duration
 }
 
-fn print_usage() {
-    println!("Usage: /path/to/config.toml");
+/// The outcome of comparing a freshly built font against the .ejf on disk.
+struct Verification {
+    result: EjfResult,
+    matches: bool
+}
+
+#[derive(Serialize)]
+struct FontsReport<'a> {
+    fonts: &'a [FontReport]
 }
 
 fn chdir(path: String) -> bool {
@@ -28,96 +45,701 @@ fn chdir(path: String) -> bool {
         return false;
     }
 
-    set_current_dir(&root.unwrap()).is_err()
+    set_current_dir(root.unwrap()).is_err()
+}
+
+/// The lockfile is stored next to the manifest, e.g. `fonts.toml` has its lockfile at `fonts.lock`.
+fn lockfile_path(config_path: &str) -> PathBuf {
+    Path::new(config_path)
+        .with_extension("lock")
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+/// Resolve a path given on the command line, which is relative to the directory the command was started from.
+fn absolute_arg(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn generate_fonts(config_path: String) {
-    // Read the configuration file.
-    let file_data = fs::read_to_string(&config_path);
+fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string(value).expect("Unable to serialize the results."));
+}
 
-    if file_data.is_err() {
-        println!("Unable to open the configuration file at '{}'.\nPlease check that the input file exists and is accessible.", &config_path);
-        exit(2);
+fn print_config_error(error: &Error, output: Output) {
+    match error {
+        Error::ConfigReadError { path, .. } => output.error(format!("Unable to open the configuration file at '{}'.\nPlease check that the input file exists and is accessible.", path)),
+        Error::ConfigParseError { path, source } => output.error(format!("Unable to parse the configuration file at '{}': {}.", path, source)),
+        error => output.error(format!("{}.", error))
     }
+}
+
+fn output_dir(args: &ManifestArgs) -> Option<PathBuf> {
+    args.output_dir.as_deref().map(absolute_arg)
+}
 
-    // Parse the configuration file as TOML.
-    let file_data = file_data.unwrap();
-    let config: Result<Config, toml::de::Error> = toml::from_str(&file_data);
+/// Apply the `--only` and `--output-dir` options to the fonts of the manifest.
+fn select_fonts(fonts: Vec<EjfConfig>, only: &[String], output_dir: Option<&Path>) -> Result<Vec<EjfConfig>, String> {
+    let is_selected = |font: &EjfConfig| get_font_name(&font.output)
+        .is_ok_and(|name| only.contains(&name));
 
-    if config.is_err() {
-        println!("Unable to parse the configuration file: {}.", config.unwrap_err().to_string());
-        exit(1)
+    for name in only {
+        if !fonts.iter().any(|font| get_font_name(&font.output).is_ok_and(|font_name| &font_name == name)) {
+            return Err(format!("There is no font named '{}' in the manifest.", name));
+        }
     }
 
-    // Change the working directory.
-    chdir(config_path);
+    Ok(fonts.into_iter()
+        .filter(|font| only.is_empty() || is_selected(font))
+        .map(|mut font| {
+            if let (Some(output_dir), Some(file_name)) = (output_dir, Path::new(&font.output).file_name()) {
+                font.output = output_dir.join(file_name).to_string_lossy().to_string();
+            }
+            if let (Some(output_dir), Some(file_name)) = (output_dir, font.kerning.as_deref().and_then(|path| Path::new(path).file_name())) {
+                font.kerning = Some(output_dir.join(file_name).to_string_lossy().to_string());
+            }
+            font
+        })
+        .collect())
+}
 
+/// Print an invalid character range, with a caret under the error in the line of the manifest declaring it.
+fn print_range_error(name: &str, section: Section, option: &str, descriptor: &str, error: &ParseError, files: &[PathBuf], output: Output) {
+    let carets = "^".repeat(error.input.chars().count().max(1));
+    match find_option(files, &section, option, descriptor) {
+        Some(location) => {
+            // The error may take more columns in the manifest than in the descriptor, because of escape sequences.
+            let (column, width) = location.span(error.column(descriptor) - 1, error.input.chars().count());
+            let line_number = location.line_number;
+            let margin = " ".repeat(line_number.to_string().len());
+            output.error(format!("{}: Error in {} at {}:{}:{}: {}", name, option, relative_path(&location.file, Path::new(".")), line_number, column, error));
+            output.error(format!(" {} |", margin));
+            output.error(format!(" {} | {}", line_number, location.line));
+            output.error(format!(" {} | {}{}", margin, " ".repeat(column - 1), "^".repeat(width)));
+        },
+        None => {
+            output.error(format!("{}: Error in {}: {}", name, option, error));
+            output.error(format!("    {}", descriptor));
+            output.error(format!("    {}{}", " ".repeat(error.column(descriptor) - 1), carets));
+        }
+    }
+}
+
+/// Print the warnings and errors of the character ranges of the fonts, and return the output of the fonts whose
+/// ranges are invalid so that their build error is not printed again.
+fn check_ranges(fonts: &[EjfConfig], files: &[PathBuf], output: Output) -> Vec<String> {
+    let mut invalid = Vec::new();
+    for font in fonts {
+        let name = get_font_name(&font.output).unwrap_or_else(|_| font.output.clone());
+        let descriptors = std::iter::once(("char_range", &font.char_range))
+            .chain(font.ignore_char_range.as_ref().map(|descriptor| ("ignore_char_range", descriptor)))
+            // The keywords of monospace are not ranges.
+            .chain(font.monospace.as_ref()
+                .filter(|descriptor| !matches!(descriptor.as_str(), "all" | "digits"))
+                .map(|descriptor| ("monospace", descriptor)));
+        for (option, descriptor) in descriptors {
+            match char_range_warnings(descriptor, &font.charsets) {
+                Ok(warnings) => for warning in warnings {
+                    output.warning(format!("{}: Warning in {}: {}", name, option, warning));
+                },
+                Err(error) => {
+                    // The error is in the definition of a charset.
+                    match error.charset.as_deref().and_then(|charset| Some((charset, font.charsets.get(charset)?))) {
+                        Some((charset, definition)) => print_range_error(&name, Section::Charset(charset), "range", definition, &error, files, output),
+                        None => print_range_error(&name, Section::Font(font), option, descriptor, &error, files, output)
+                    }
+                    invalid.push(font.output.clone());
+                }
+            }
+        }
+    }
+    invalid
+}
+
+/// The fonts of the manifest, along with the output of the ones whose ranges are invalid.
+fn read_config(args: &ManifestArgs, output: Output) -> (Vec<EjfConfig>, Vec<String>) {
+    let config_path = args.manifest.clone().unwrap_or_default();
+    let output_dir = output_dir(args);
+
+    match load_config(Path::new(&config_path)) {
+        Ok(manifest) => {
+            // Change the working directory.
+            chdir(config_path);
+
+            let fonts = select_fonts(manifest.fonts, &args.only, output_dir.as_deref())
+                .unwrap_or_else(|message| {
+                    output.error(message);
+                    exit(2);
+                });
+            let invalid_ranges = check_ranges(&fonts, &manifest.files, output);
+            (fonts, invalid_ranges)
+        },
+        Err(error) => {
+            print_config_error(&error, output);
+            exit(match error {
+                Error::ConfigReadError { .. } => 2,
+                _ => 1
+            });
+        }
+    }
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Run the given task for every font on a pool of `jobs` threads, displaying a progress bar for each of them.
+///
+/// The fonts with the most characters are started first so that they don't end up running alone at the end,
+/// and the CPUs are shared between the characters of the fonts running at the same time. The results are
+/// returned in the same order as the fonts.
+fn process_fonts<T, F, S>(fonts: Vec<EjfConfig>, jobs: Option<NonZeroUsize>, output: Output, task: F, status: S) -> Vec<ThreadData<T>>
+    where
+        T: Send + 'static,
+        F: Fn(&EjfConfig, FontBuilder) -> Result<T, Error> + Send + Sync + 'static,
+        S: Fn(&T) -> String + Send + Sync + 'static
+{
+    let task = Arc::new(task);
+    let status = Arc::new(status);
     let spinner_style_progress = ProgressStyle::with_template("{prefix:32.bold.dim} {wide_bar:.cyan/blue} {pos:>5}/{len}").unwrap().tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
     let spinner_style_done = ProgressStyle::with_template("{prefix:32.bold.dim} {msg}").unwrap();
 
-    let fonts = config.unwrap().font;
     let num_fonts = fonts.len();
-    let progress = MultiProgress::new();
-    let mut threads: Vec<JoinHandle<ThreadData>> = Vec::new();
-    let mut i = 0;
-    for font in fonts {
-        i += 1;
-        let name = get_font_name(&font.output).unwrap_or_default();
-        let pb = progress.add(ProgressBar::new(0));
-        let style_done = spinner_style_done.clone();
-        pb.set_style(spinner_style_progress.clone());
-        pb.set_prefix(format!("[{}/{}] {}", i, num_fonts, name.to_string()));
-
-        threads.push(thread::spawn(move || {            
-            let result = build_ejf(&font,  | progress | {
-                pb.set_length(progress.1 as u64);
-                pb.set_position(progress.0 as u64);                
-            });
+    let jobs = jobs.map_or_else(available_threads, NonZeroUsize::get).clamp(1, max(1, num_fonts));
+    let render_threads = available_threads().div_ceil(jobs);
+    let progress = output.progress();
+
+    // The queue is sorted by increasing number of characters, so that the largest font is taken first.
+    let mut queue: Vec<(usize, EjfConfig, ProgressBar)> = fonts.into_iter()
+        .enumerate()
+        .map(|(i, font)| {
+            let name = get_font_name(&font.output).unwrap_or_default();
+            let pb = progress.add(ProgressBar::new(0));
+            pb.set_style(spinner_style_done.clone());
+            pb.set_prefix(format!("[{}/{}] {}", i + 1, num_fonts, name));
+            pb.set_message("Waiting.");
+            (i, font, pb)
+        })
+        .collect();
+    queue.sort_by_cached_key(|(_, font, _)| build_char_list(font).map_or(0, |chars| chars.len()));
+    let queue = Arc::new(Mutex::new(queue));
+
+    let threads: Vec<_> = (0..jobs)
+        .map(|_| {
+            let queue = queue.clone();
+            let task = task.clone();
+            let status = status.clone();
+            let style_progress = spinner_style_progress.clone();
+            let style_done = spinner_style_done.clone();
 
-            let status = match &result {
-                Ok(result) => format!("Done, height: {}px, space width: {}px", &result.height, &result.space_width),
-                Err(_) => "Failed.".to_string()
-            };
+            thread::spawn(move || {
+                let mut results = Vec::new();
+                while let Some((i, font, pb)) = queue.lock().expect("The queue is never poisoned.").pop() {
+                    pb.set_style(style_progress.clone());
+                    let progress_bar = pb.clone();
 
-            pb.set_style(style_done);
-            pb.finish_with_message(format!("{}", status));
+                    // A panic is reported as a failure of this font, so that the other ones can still complete.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let builder = FontBuilder::from_config(&font)?
+                            .threads(render_threads)
+                            .on_progress(move |progress| update_progress(&progress_bar, progress));
+                        task(&font, builder)
+                    })).unwrap_or_else(|payload| Err(Error::panic(payload)));
 
-            ThreadData {
-                input: font.input,
-                output: font.output,
-                result
+                    let status = match &result {
+                        Ok(result) => status(result),
+                        Err(_) => "Failed.".to_string()
+                    };
+
+                    pb.set_style(style_done.clone());
+                    pb.finish_with_message(status);
+
+                    results.push((i, ThreadData {
+                        font,
+                        result
+                    }));
+                }
+                results
+            })
+        })
+        .collect();
+
+    let mut results: Vec<(usize, ThreadData<T>)> = threads.into_iter()
+        .flat_map(|thread| thread.join().expect("Panics are caught by the font threads."))
+        .collect();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, data)| data).collect()
+}
+
+fn update_progress(pb: &ProgressBar, progress: (i32, i32)) {
+    pb.set_length(progress.1 as u64);
+    pb.set_position(progress.0 as u64);
+}
+
+fn format_result(result: &EjfResult) -> String {
+    format!("height: {}px, space width: {}px", &result.height, &result.space_width)
+}
+
+/// The message of the error, followed by the messages of its causes.
+fn error_chain(error: &Error) -> Vec<String> {
+    let mut messages = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    messages
+}
+
+fn print_error_chain(prefix: &str, messages: &[String], output: Output) {
+    if let Some((message, causes)) = messages.split_first() {
+        output.error(format!("{}: {}.", prefix, message));
+        for cause in causes {
+            output.error(format!("    Caused by: {}", cause));
+        }
+    }
+}
+
+/// Turn the result of a font into its report, using the given function to report the successful ones.
+fn to_report<T, F>(data: &ThreadData<T>, success: F) -> FontReport
+    where F: Fn(&T) -> FontReport
+{
+    match &data.result {
+        Ok(result) => success(result),
+        Err(error) => FontReport::new(&data.font, FontStatus::Failed)
+            .with_error(error_chain(error))
+    }
+}
+
+/// Print the results as JSON if requested, otherwise the fonts that differ and the error of every font that
+/// failed, except for the fonts whose ranges are invalid since their error was already printed.
+fn print_reports(reports: &[FontReport], invalid_ranges: &[String], output: Output) {
+    if output.json {
+        print_json(&FontsReport { fonts: reports });
+        return;
+    }
+
+    for report in reports.iter().filter(|report| !invalid_ranges.contains(&report.output)) {
+        if let Some(error) = &report.error {
+            let messages: Vec<String> = std::iter::once(error.clone())
+                .chain(report.caused_by.iter().cloned())
+                .collect();
+            print_error_chain(&report.name, &messages, output);
+        }
+
+        // The progress bars are not shown when quiet or outside of a terminal, e.g. in CI.
+        if report.status == FontStatus::Differs {
+            output.error(format!("{}: differs from the generated font.", report.name));
+        }
+    }
+}
+
+fn is_successful(reports: &[FontReport]) -> bool {
+    reports.iter().all(|report| report.status != FontStatus::Failed)
+}
+
+/// Build the fonts that changed since the last build (or all of them if forced).
+fn build_fonts(fonts: Vec<EjfConfig>, lockfile_path: &Path, force: bool, jobs: Option<NonZeroUsize>, output: Output) -> Vec<FontReport> {
+    let previous_lockfile = Lockfile::read(lockfile_path);
+    let outputs: Vec<String> = fonts.iter().map(|font| font.output.clone()).collect();
+
+    // Fonts whose fingerprint cannot be determined (e.g. missing input) are built to report the error.
+    let (up_to_date, outdated): (Vec<EjfConfig>, Vec<EjfConfig>) = fonts.into_iter()
+        .partition(|font| !force && lockfile::fingerprint(font)
+            .is_ok_and(|fingerprint| lockfile::is_up_to_date(previous_lockfile.find(&font.output), &fingerprint))
+            && font.kerning.as_ref().is_none_or(|kerning| Path::new(kerning).exists()));
+
+    if !up_to_date.is_empty() {
+        output.message(format!("{} font(s) up to date, skipped.", up_to_date.len()));
+    }
+
+    let results = process_fonts(outdated, jobs, output, |font, builder| {
+        let fingerprint = lockfile::fingerprint(font)?;
+        for path in std::iter::once(&font.output).chain(&font.kerning) {
+            if let Some(output_dir) = Path::new(path).parent() {
+                fs::create_dir_all(output_dir)
+                    .map_err(|e| Error::io(&output_dir.to_string_lossy(), e))?;
             }
-        }));
+        }
+
+        let start_time = Instant::now();
+        let result = builder.build_to_file(&font.output)?;
+        if let Some(kerning) = &font.kerning {
+            builder.build_kerning_to_file(kerning)?;
+        }
+        Ok(BuildOutcome {
+            result,
+            lock: LockEntry {
+                output: font.output.clone(),
+                fingerprint,
+                checksum: lockfile::checksum(&font.output)
+                    .map_err(|e| Error::io(&font.output, e))?
+            },
+            build_time: start_time.elapsed()
+        })
+    }, |outcome| format!("Done, {}", format_result(&outcome.result)));
+
+    // Fonts that were not part of this build keep their entry, failed ones lose it so that they're retried.
+    let mut lockfile = Lockfile {
+        font: previous_lockfile.font.iter()
+            .filter(|entry| !up_to_date.iter().chain(results.iter().map(|data| &data.font))
+                .any(|font| font.output == entry.output))
+            .chain(up_to_date.iter().filter_map(|font| previous_lockfile.find(&font.output)))
+            .cloned()
+            .chain(results.iter()
+                .filter_map(|data| data.result.as_ref().ok())
+                .map(|outcome| outcome.lock.clone()))
+            .collect()
+    };
+    lockfile.font.sort_by(|a, b| a.output.cmp(&b.output));
+    if let Err(e) = lockfile.write(lockfile_path) {
+        output.error(format!("Unable to write the lockfile at '{}': {}", lockfile_path.display(), e));
+    }
+
+    let mut reports: Vec<FontReport> = up_to_date.iter()
+        .map(|font| {
+            let report = FontReport::new(font, FontStatus::Skipped);
+            match read_info(&font.output) {
+                Ok((size, info)) => report.with_info(&info, size),
+                Err(_) => report
+            }
+        })
+        .chain(results.iter().map(|data| to_report(data, |outcome| {
+            FontReport::new(&data.font, FontStatus::Built)
+                .with_result(&outcome.result)
+                .with_build_time(outcome.build_time)
+        })))
+        .collect();
+
+    // Report the fonts in the same order as the manifest.
+    reports.sort_by_key(|report| outputs.iter().position(|output| *output == report.output));
+    reports
+}
+
+fn generate_fonts(args: cli::BuildArgs, output: Output) -> bool {
+    let lockfile_path = lockfile_path(args.manifest.manifest.as_deref().unwrap_or_default());
+    let report_path = args.report.as_deref().map(absolute_arg);
+    let (fonts, invalid_ranges) = read_config(&args.manifest, output);
+    let reports = build_fonts(fonts, &lockfile_path, args.force, args.manifest.jobs, output);
+    print_reports(&reports, &invalid_ranges, output);
+
+    if let Some(report_path) = report_path {
+        let data = serde_json::to_string_pretty(&FontsReport { fonts: &reports })
+            .expect("Unable to serialize the report.");
+        if let Err(e) = fs::write(&report_path, data) {
+            output.error(format!("Unable to write the report at '{}': {}", report_path.display(), e));
+            return false;
+        }
+    }
+
+    is_successful(&reports)
+}
+
+fn verify_fonts(args: ManifestArgs, output: Output) -> bool {
+    let (fonts, invalid_ranges) = read_config(&args, output);
+
+    let results = process_fonts(fonts, args.jobs, output, |font, builder| {
+        // Build the font in memory and compare it with the one on disk.
+        let mut generated = Cursor::new(Vec::new());
+        let result = builder.build_to_writer(&mut generated)?;
+        let existing = fs::read(&font.output)
+            .map_err(|e| Error::io(&font.output, e))?;
+
+        Ok(Verification {
+            result,
+            matches: existing == generated.into_inner()
+        })
+    }, |verification| match verification.matches {
+        true => format!("Up to date, {}", format_result(&verification.result)),
+        false => "Differs from the generated font.".to_string()
+    });
+
+    let reports: Vec<FontReport> = results.iter()
+        .map(|data| to_report(data, |verification| {
+            let status = if verification.matches { FontStatus::UpToDate } else { FontStatus::Differs };
+            FontReport::new(&data.font, status).with_result(&verification.result)
+        }))
+        .collect();
+    print_reports(&reports, &invalid_ranges, output);
+
+    let has_drift = reports.iter().any(|report| report.status == FontStatus::Differs);
+    if has_drift {
+        output.error("One or more fonts differ from their manifest declaration, regenerate them to fix this.");
     }
 
-    for thread in threads {
-        let data = thread.join().unwrap();
-
-        let message: Option<String> = match data.result {
-            Ok(_) => None,
-            Err(error) => Some(match error {
-                Error::FreeTypeError(_) => "Unable to initialize FreeType.".to_string(),
-                Error::ImageError(_) => "Unable to generate the image files for one or more characters.".to_string(),
-                Error::IoError(e) => format!("Unable to read or write the .ejf file at '{}': {}", &data.output, e.to_string()),
-                Error::MetricsError => "Unable to determine the metrics for one or more characters.".to_string(),
-                Error::XmlWriterError(_) => "Unable to write the header XML file.".to_string(),
-                Error::ZipWriterError(_) => "Unable to file the ZIP file (.ejf).".to_string(),
-                Error::RangeParseError(e) => format!("Unable to parse the given character range: {}", e.message),
-                Error::NameError => "Unable to determine the name of the resulting font (.ejf) based on the path.".to_string()
+    is_successful(&reports) && !has_drift
+}
+
+/// Rebuild the fonts whenever the manifest or one of the input fonts changes.
+fn watch_fonts(args: ManifestArgs, output: Output) -> bool {
+    // The manifest is reloaded after changing the working directory, so its path must be absolute.
+    let config_path = watch::absolute_path(Path::new(args.manifest.as_deref().unwrap_or_default()))
+        .to_string_lossy()
+        .to_string();
+    let lockfile_path = lockfile_path(&config_path);
+    let output_dir = output_dir(&args);
+    chdir(config_path.clone());
+
+    let mut watcher = match FileWatcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            output.error(format!("Unable to watch for file changes: {}", e));
+            return false;
+        }
+    };
+
+    loop {
+        let mut files = vec![PathBuf::from(&config_path)];
+        let fonts = load_config(Path::new(&config_path))
+            .map_err(|error| {
+                // Keep watching the file in error, which can be an included file, to reload it once fixed.
+                if let Error::ConfigReadError { path, .. } | Error::ConfigParseError { path, .. } = &error {
+                    files.push(PathBuf::from(path));
+                }
+                print_config_error(&error, output);
             })
+            .and_then(|manifest| {
+                // Also watch the files included by the manifest.
+                files = manifest.files;
+                select_fonts(manifest.fonts, &args.only, output_dir.as_deref())
+                    .map_err(|message| output.error(message))
+            });
+
+        if let Ok(fonts) = fonts {
+            let invalid_ranges = check_ranges(&fonts, &files, output);
+            files.extend(fonts.iter().map(|font| PathBuf::from(&font.input)));
+            files.extend(fonts.iter().filter_map(|font| font.fallback_image.as_ref()).map(PathBuf::from));
+            files.extend(fonts.iter()
+                .filter_map(|font| font.chars_from.as_deref())
+                .filter_map(|patterns| extract::matching_files(patterns).ok())
+                .flatten());
+            let reports = build_fonts(fonts, &lockfile_path, false, args.jobs, output);
+            print_reports(&reports, &invalid_ranges, output);
+        }
+
+        if let Err(e) = watcher.set_files(&files) {
+            output.error(format!("Unable to watch for file changes: {}", e));
+            return false;
+        }
+
+        output.message("\nWatching for changes, press Ctrl+C to stop.\n");
+        watcher.wait_for_change();
+    }
+}
+
+/// Read an existing .ejf, along with its size.
+fn read_info(path: &str) -> Result<(u64, EjfInfo), Error> {
+    let reader = File::open(path).map_err(|e| Error::io(path, e))?;
+    let size = reader.metadata().map_err(|e| Error::io(path, e))?.len();
+    Ok((size, read_ejf(reader)?))
+}
+
+fn inspect_font(file: PathBuf, output: Output) -> bool {
+    let (size, info) = match read_info(&file.to_string_lossy()) {
+        Ok(info) => info,
+        Err(error) => {
+            print_error_chain(&file.to_string_lossy(), &error_chain(&error), output);
+            return false;
+        }
+    };
+
+    let compression = match info.compression {
+        Some(compression) => serde_json::to_value(compression).unwrap_or_default(),
+        None => serde_json::Value::Null
+    };
+    let char_range = format_char_range(&info.chars);
+
+    if output.json {
+        print_json(&serde_json::json!({
+            "name": info.name,
+            "height": info.height,
+            "baseline": info.baseline,
+            "space_width": info.space_width,
+            "glyph_count": info.chars.len(),
+            "char_range": char_range,
+            "entries": info.entries,
+            "compression": compression,
+            "size": size
+        }));
+    } else if !output.quiet {
+        println!("Name:        {}", info.name);
+        println!("Height:      {}px", info.height);
+        println!("Baseline:    {}px", info.baseline);
+        println!("Space width: {}px", info.space_width);
+        println!("Characters:  {} ({})", info.chars.len(), char_range);
+        println!("Entries:     {}", info.entries);
+        println!("Compression: {}", compression.as_str().unwrap_or("other"));
+        println!("Size:        {} bytes", size);
+    }
+
+    true
+}
+
+/// The path as a string with `/` separators, relative to the given directory if it is inside of it.
+fn relative_path(path: &Path, root: &Path) -> String {
+    let path = watch::absolute_path(path);
+    let root = fs::canonicalize(root).unwrap_or_else(|_| absolute_arg(root));
+    let path = path.strip_prefix(root).unwrap_or(&path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Write the result of a conversion to the given file, or to the standard output.
+fn write_result(content: &str, file: Option<&Path>, output: Output) -> bool {
+    match file {
+        Some(file) => match fs::write(file, content) {
+            Ok(()) => {
+                output.message(format!("Written to '{}'.", file.display()));
+                true
+            },
+            Err(error) => {
+                print_error_chain(&file.to_string_lossy(), &error_chain(&Error::io(&file.to_string_lossy(), error)), output);
+                false
+            }
+        },
+        None => {
+            print!("{}", content);
+            true
+        }
+    }
+}
+
+fn export_fonts_list(args: ExportArgs, output: Output) -> bool {
+    // Paths given on the command line are relative to the directory the command was started from.
+    let root = args.root.as_deref().map(absolute_arg);
+    let output_file = args.output.as_deref().map(absolute_arg);
+    let manifest_args = ManifestArgs {
+        manifest: Some(args.manifest),
+        only: args.only,
+        output_dir: None,
+        jobs: None
+    };
+    let (fonts, invalid_ranges) = read_config(&manifest_args, output);
+    let root = root.unwrap_or_else(|| PathBuf::from("."));
+
+    let mut success = true;
+    let mut content = String::new();
+    for font in fonts {
+        match build_char_list(&font) {
+            Ok(chars) => {
+                let entry = FontsListEntry {
+                    path: relative_path(Path::new(&font.output), &root),
+                    range: Some(fonts_list::format_range(&chars)).filter(|range| !range.is_empty()),
+                    bpp: font.bpp
+                };
+                content.push_str(&format!("{}\n", entry));
+            },
+            Err(error) => {
+                if !invalid_ranges.contains(&font.output) {
+                    print_error_chain(&get_font_name(&font.output).unwrap_or(font.output), &error_chain(&error), output);
+                }
+                success = false;
+            }
+        }
+    }
+
+    success && write_result(&content, output_file.as_deref(), output)
+}
+
+/// Quote a string for the manifest.
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn import_fonts_list(args: ImportArgs, output: Output) -> bool {
+    let path = args.file.to_string_lossy().to_string();
+    let entries = fs::read_to_string(&args.file)
+        .map_err(|e| Error::io(&path, e))
+        .and_then(|content| fonts_list::parse(&content));
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(error) => {
+            print_error_chain(&path, &error_chain(&error), output);
+            return false;
+        }
+    };
+
+    // The paths of the manifest are relative to the manifest itself.
+    let root = args.root.clone()
+        .unwrap_or_else(|| args.file.parent().map(Path::to_path_buf).unwrap_or_default());
+    let manifest_root = args.output.as_deref()
+        .and_then(Path::parent)
+        .filter(|root| !root.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    let mut success = true;
+    let mut fonts = Vec::new();
+    for entry in entries {
+        let ejf_path = root.join(entry.path.trim_start_matches('/'));
+
+        // Without a range, the font contains all the characters of the existing .ejf.
+        let (char_range, chars) = match &entry.range {
+            Some(range) => {
+                let char_range = fonts_list::to_char_range(range);
+                let chars = char_range::char_range(&char_range, false, None).unwrap_or_default();
+                (char_range, chars)
+            },
+            None => match read_info(&ejf_path.to_string_lossy()) {
+                Ok((_, info)) => {
+                    let chars: Vec<char> = info.chars.iter().copied().filter(|ch| *ch != '\0').collect();
+                    (format_char_range(&chars), info.chars)
+                },
+                Err(_) => {
+                    output.error(format!("{}: No range and no existing .ejf to read the characters from.", entry.path));
+                    success = false;
+                    continue;
+                }
+            }
         };
-    
-        if message.is_some() {
-            println!("{}: {}", data.input, message.unwrap());
+
+        // The NULL character is added separately, so that the other control characters can be skipped unless
+        // the font contains some of them.
+        let add_null_character = chars.contains(&'\0');
+        let skip_control_characters = !chars.iter().any(|ch| *ch != '\0' && ch.is_control());
+
+        let mut content = String::new();
+        content.push_str("[[font]]\n");
+        content.push_str("# The input font and its size cannot be determined from the .fonts.list.\n");
+        content.push_str("input = \"\"\n");
+        content.push_str(&format!("output = {}\n", toml_string(&relative_path(&ejf_path, &manifest_root))));
+        content.push_str("size = 12\n");
+        content.push_str(&format!("char_range = {}\n", toml_string(&char_range)));
+        content.push_str(&format!("skip_control_characters = {}\n", skip_control_characters));
+        if add_null_character {
+            content.push_str("add_null_character = true\n");
+        }
+        if let Some(bpp) = entry.bpp {
+            content.push_str(&format!("bpp = {}\n", bpp));
         }
+        fonts.push(content);
     }
+
+    success && write_result(&fonts.join("\n"), args.output.as_deref(), output)
 }
 
-fn main() {    
-    println!("EJF Font Generator\n");
+fn main() {
+    let cli = Cli::parse();
+    let output = Output {
+        quiet: cli.quiet,
+        json: cli.json
+    };
+
+    // Keep the standard output clean for the commands printing their result to it.
+    if !cli.command.as_ref().is_some_and(Command::prints_result) {
+        output.message("EJF Font Generator\n");
+    }
+
+    let success = match cli.command {
+        Some(Command::Build(args)) => generate_fonts(args, output),
+        Some(Command::Verify(args)) => verify_fonts(args, output),
+        Some(Command::Watch(args)) => watch_fonts(args, output),
+        Some(Command::Inspect { file }) => inspect_font(file, output),
+        Some(Command::ExportFontsList(args)) => export_fonts_list(args, output),
+        Some(Command::ImportFontsList(args)) => import_fonts_list(args, output),
+        None => generate_fonts(cli.build, output)
+    };
 
-    match args().nth(1) {
-        Some(config_path) => generate_fonts(config_path),
-        None => print_usage()
+    if !success {
+        exit(1);
     }
 }
@@ -0,0 +1,88 @@
+// Reads the fonts declared in a manifest (.toml), along with the charsets it defines or includes.
+
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{char_range::Charsets, ejf::{EjfConfig, Error}};
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    /// Files defining more charsets, relative to the manifest.
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    charset: BTreeMap<String, CharsetConfig>,
+    font: Vec<EjfConfig>
+}
+
+/// A file included by the manifest, which can only define charsets.
+#[derive(Debug, Deserialize)]
+struct CharsetFile {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    charset: BTreeMap<String, CharsetConfig>
+}
+
+#[derive(Debug, Deserialize)]
+struct CharsetConfig {
+    range: String
+}
+
+/// The fonts of a manifest, along with the files it was read from.
+#[non_exhaustive]
+pub struct Manifest {
+    /// The fonts, with the charsets of the manifest and of its included files.
+    pub fonts: Vec<EjfConfig>,
+    /// The manifest and the files it includes.
+    pub files: Vec<PathBuf>
+}
+
+fn load_toml<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let display_path = path.to_string_lossy().to_string();
+    let file_data = fs::read_to_string(path)
+        .map_err(|source| Error::ConfigReadError { path: display_path.clone(), source })?;
+    toml::from_str(&file_data)
+        .map_err(|source| Error::ConfigParseError { path: display_path, source })
+}
+
+/// Add the charsets of the included files, whose paths are relative to the file including them. The charsets
+/// of a file take precedence over the ones it includes, and files included more than once are only read once.
+fn load_includes(path: &Path, includes: &[String], charsets: &mut Charsets, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let root = path.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        let include_path = root.join(include);
+        let canonical_path = fs::canonicalize(&include_path)
+            .map_err(|source| Error::ConfigReadError { path: include_path.to_string_lossy().to_string(), source })?;
+        if files.contains(&canonical_path) {
+            continue;
+        }
+        files.push(canonical_path);
+
+        let file: CharsetFile = load_toml(&include_path)?;
+        load_includes(&include_path, &file.include, charsets, files)?;
+        charsets.extend(file.charset.into_iter().map(|(name, charset)| (name, charset.range)));
+    }
+    Ok(())
+}
+
+/// Read the fonts of a manifest. The paths of the fonts are left as written, relative to the directory of the
+/// manifest.
+pub fn load_config(config_path: &Path) -> Result<Manifest, Error> {
+    // Read the configuration file and parse it as TOML.
+    let config: Config = load_toml(config_path)?;
+
+    // Gather the charsets from the included files, then from the manifest itself.
+    let mut charsets = Charsets::new();
+    let mut files = vec![fs::canonicalize(config_path).unwrap_or_else(|_| config_path.to_path_buf())];
+    load_includes(config_path, &config.include, &mut charsets, &mut files)?;
+    charsets.extend(config.charset.into_iter().map(|(name, charset)| (name, charset.range)));
+
+    let fonts = config.font.into_iter()
+        .map(|mut font| {
+            font.charsets = charsets.clone();
+            font
+        })
+        .collect();
+    Ok(Manifest { fonts, files })
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+use serde::Serialize;
+
+use ejf_utils::ejf::{EjfConfig, EjfInfo, EjfResult, get_font_name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontStatus {
+    /// The font was generated.
+    Built,
+    /// The font was not generated because it's already up to date.
+    Skipped,
+    /// The font on disk is identical to the generated one.
+    UpToDate,
+    /// The font on disk differs from the generated one.
+    Differs,
+    /// The font could not be generated.
+    Failed
+}
+
+/// The outcome of processing a single font, as printed with `--json` or written with `--report`.
+///
+/// The statistics are only known for the fonts that were built or read back from disk, and the missing
+/// and clipped glyphs only for the ones that were built.
+#[derive(Debug, Serialize)]
+pub struct FontReport {
+    pub name: String,
+    pub input: String,
+    pub output: String,
+    pub status: FontStatus,
+    pub size: u32,
+    pub glyph_count: Option<usize>,
+    pub height: Option<u32>,
+    pub baseline: Option<u32>,
+    pub space_width: Option<u32>,
+    pub missing_glyphs: Option<Vec<String>>,
+    pub clipped_glyphs: Option<Vec<String>>,
+    /// Size of the .ejf file, in bytes.
+    pub archive_size: Option<u64>,
+    pub build_time_ms: Option<u128>,
+    pub error: Option<String>,
+    /// The underlying causes of the error, from the closest to the furthest.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub caused_by: Vec<String>
+}
+
+fn format_chars(chars: &[char]) -> Vec<String> {
+    chars.iter().map(|ch| format!("0x{:x}", *ch as u32)).collect()
+}
+
+impl FontReport {
+    pub fn new(font: &EjfConfig, status: FontStatus) -> FontReport {
+        FontReport {
+            name: get_font_name(&font.output).unwrap_or_default(),
+            input: font.input.clone(),
+            output: font.output.clone(),
+            status,
+            size: font.size,
+            glyph_count: None,
+            height: None,
+            baseline: None,
+            space_width: None,
+            missing_glyphs: None,
+            clipped_glyphs: None,
+            archive_size: None,
+            build_time_ms: None,
+            error: None,
+            caused_by: Vec::new()
+        }
+    }
+
+    pub fn with_result(mut self, result: &EjfResult) -> FontReport {
+        self.glyph_count = Some(result.glyph_count);
+        self.height = Some(result.height);
+        self.baseline = Some(result.baseline);
+        self.space_width = Some(result.space_width);
+        self.missing_glyphs = Some(format_chars(&result.missing_glyphs));
+        self.clipped_glyphs = Some(format_chars(&result.clipped_glyphs));
+        self.archive_size = Some(result.archive_size);
+        self
+    }
+
+    /// Fill in the statistics from a font that was not built but read back from disk.
+    pub fn with_info(mut self, info: &EjfInfo, archive_size: u64) -> FontReport {
+        self.glyph_count = Some(info.chars.len());
+        self.height = Some(info.height);
+        // The header always holds the same baseline, not the one of the font.
+        self.space_width = Some(info.space_width);
+        self.archive_size = Some(archive_size);
+        self
+    }
+
+    pub fn with_build_time(mut self, build_time: Duration) -> FontReport {
+        self.build_time_ms = Some(build_time.as_millis());
+        self
+    }
+
+    /// Record the error, given as its message followed by the messages of its causes.
+    pub fn with_error(mut self, mut messages: Vec<String>) -> FontReport {
+        if !messages.is_empty() {
+            self.error = Some(messages.remove(0));
+            self.caused_by = messages;
+        }
+        self
+    }
+}
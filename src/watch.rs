@@ -0,0 +1,82 @@
+use std::{collections::HashSet, fs, path::{Path, PathBuf}, sync::mpsc::{channel, Receiver}, time::Duration};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Events arriving within this delay of each other are handled as a single change, since editors
+/// usually write a file in several steps.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(250);
+
+/// Watches a set of files for changes.
+///
+/// The parent directories are watched instead of the files themselves so that files which are replaced
+/// rather than modified in place (as most editors do when saving) are still detected.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    directories: HashSet<PathBuf>,
+    files: HashSet<PathBuf>
+}
+
+/// Make the path absolute without requiring the file itself to exist, only its parent directory.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new(".")
+    };
+
+    match (fs::canonicalize(parent), path.file_name()) {
+        (Ok(parent), Some(file_name)) => parent.join(file_name),
+        _ => path.to_path_buf()
+    }
+}
+
+impl FileWatcher {
+    pub fn new() -> notify::Result<FileWatcher> {
+        let (sender, events) = channel();
+        Ok(FileWatcher {
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            directories: HashSet::new(),
+            files: HashSet::new()
+        })
+    }
+
+    /// Replace the set of watched files.
+    pub fn set_files(&mut self, files: &[PathBuf]) -> notify::Result<()> {
+        self.files = files.iter().map(|file| absolute_path(file)).collect();
+
+        let directories: HashSet<PathBuf> = self.files.iter()
+            .filter_map(|file| file.parent().map(Path::to_path_buf))
+            .collect();
+
+        for directory in self.directories.difference(&directories) {
+            self.watcher.unwatch(directory)?;
+        }
+
+        for directory in directories.difference(&self.directories) {
+            self.watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        self.directories = directories;
+        Ok(())
+    }
+
+    fn is_relevant(&self, event: &notify::Result<Event>) -> bool {
+        match event {
+            Ok(event) => !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| self.files.contains(path)),
+            Err(_) => false
+        }
+    }
+
+    /// Block until one of the watched files changes.
+    pub fn wait_for_change(&self) {
+        while let Ok(event) = self.events.recv() {
+            if self.is_relevant(&event) {
+                break;
+            }
+        }
+
+        // Discard the rest of the events belonging to the same change.
+        while self.events.recv_timeout(DEBOUNCE_DELAY).is_ok() {}
+    }
+}